@@ -1,146 +1,323 @@
 use std::{
+    env, fs,
+    io::{self, Write},
+    ops::Range,
+    path::Path,
     str::FromStr,
-    sync::{
-        mpsc::{self, Sender},
-        Arc,
-    },
+    sync::{mpsc, Arc},
     thread,
-    time::Duration,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use color_eyre::Result;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use futures_util::{
-    stream::{SplitSink, SplitStream},
-    SinkExt, StreamExt,
+use crossterm::{
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        MouseEventKind, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    terminal::{supports_keyboard_enhancement, EnterAlternateScreen},
 };
-use http::Uri;
 use ratatui::{
-    layout::{Constraint, Layout},
-    style::Stylize,
-    text::{Line, Text},
-    widgets::{Block, List, ListItem, Paragraph},
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
     DefaultTerminal, Frame,
 };
 use std::sync::Mutex as SyncMutex;
-use tokio::sync::Mutex;
-use tokio_websockets::{ClientBuilder, MaybeTlsStream, Message, WebSocketStream};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-type WS = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+use crate::{
+    certificate::PeerCertificate,
+    connection::{
+        parse_protocol, ConnectOptions, ConnectionState, Header, ResolveOptions, TlsOptions,
+    },
+    filter::Query,
+    history::History,
+    input::LineEditor,
+    json::{self, Token},
+    message::{
+        hex_dump, insert_chronologically, Author, BinaryView, ChatMessage, Content, Timestamp,
+        TimestampFormat,
+    },
+    proxy::Proxy,
+    replay::{differing_ranges, same_response, Recording, Replay, ReplayTiming},
+    session::{Session, SessionOptions},
+    transcript::{self, Transcript},
+};
 
-type ArcSink =
-    Arc<Mutex<Option<SplitSink<WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>, Message>>>>;
+/// What each key does, as shown by `F1`.
+const KEY_BINDINGS: &[(&str, &str)] = &[
+    ("Esc, Ctrl-C", "Quit"),
+    (
+        "Tab",
+        "Cycle between URL, headers, subprotocols and chatting",
+    ),
+    (
+        "Ctrl-R",
+        "Reconnect with the current URL, headers and subprotocols",
+    ),
+    ("Ctrl-T", "Show the TLS details of the connection"),
+    ("F1", "Show this help"),
+    ("F2", "Toggle the connection info panel"),
+    ("F3", "Cycle binary views (hex, base64, UTF-8)"),
+    ("F4", "Cycle timestamp formats"),
+    (
+        "F5, Shift-F5",
+        "Expand or collapse JSON (Shift for all messages)",
+    ),
+    ("F6, Ctrl-Up/Down", "Select a message and inspect it"),
+    (
+        "F7",
+        "Switch the inspector between raw, JSON and hex payloads",
+    ),
+    ("Shift-Up/Down", "Scroll the inspector"),
+    ("Ctrl-G", "Search the log"),
+    ("Alt-Up/Down", "Jump between search matches"),
+    ("F8", "Filter the log"),
+    ("Up/Down", "Recall sent messages"),
+    ("Ctrl-F", "Search sent messages"),
+    ("Alt-Enter", "Add a line to the message"),
+    ("Ctrl-O", "Write the message in $EDITOR"),
+    ("Ctrl-P", "Send a ping"),
+    (
+        "PageUp/PageDown",
+        "Scroll the messages, as does the mouse wheel",
+    ),
+    (
+        "Ctrl-Home/End",
+        "Scroll to the top, or follow the latest messages",
+    ),
+    ("Ctrl-S", "Export the messages as JSON Lines"),
+    (
+        "F9",
+        "Replay the `--replay` transcript and compare the responses",
+    ),
+];
 
 pub struct App {
-    sink: ArcSink,
-    sender: Sender<String>,
+    session: Session,
     running: bool,
     messages: Arc<SyncMutex<Vec<ChatMessage>>>,
-    text_input_content: String,
-    url_content: String,
+    /// Where messages are logged as they come, with `--log`.
+    transcript: Option<Arc<SyncMutex<Transcript>>>,
+    text_input_content: LineEditor,
+    url_content: LineEditor,
+    headers: Vec<Header>,
+    header_input_content: LineEditor,
+    protocols: Vec<String>,
+    tls: TlsOptions,
+    proxy: Option<Proxy>,
+    resolve: ResolveOptions,
+    protocol_input_content: LineEditor,
+    show_connection_info: bool,
+    /// Whether the TLS details of the connection are shown over the log.
+    show_tls: bool,
+    /// How far the key bindings shown over the log are scrolled, `None` when they are hidden.
+    help_scroll: Option<u16>,
+    binary_view: BinaryView,
+    timestamp_format: TimestampFormat,
     input_field: InputField,
-    error_while_sending: bool,
+    send_error: Option<&'static str>,
+    invalid_entry: bool,
+    /// First line shown in the message log while scrolled up, `None` when following the
+    /// latest messages.
+    scroll: Option<usize>,
+    /// How many messages there were when the log stopped following the latest ones.
+    unseen_from: usize,
+    /// Size of the message log as of the last draw, for scrolling by pages.
+    view_height: usize,
+    total_lines: usize,
+    /// The message shown at the bottom of the log as of the last draw.
+    last_visible: Option<usize>,
+    /// The message shown in the inspector pane, if open.
+    selected: Option<usize>,
+    /// A message the log should scroll to on the next draw.
+    reveal: Option<usize>,
+    inspector_tab: InspectorTab,
+    inspector_scroll: u16,
+    /// The prompt being typed in instead of a message, if any.
+    prompt: Option<Prompt>,
+    prompt_input: LineEditor,
+    prompt_error: Option<String>,
+    /// Highlighted in the log, as typed after `Ctrl-G`.
+    search: Option<(String, Query)>,
+    /// Messages matching the search as of the last draw, oldest first.
+    search_matches: Vec<usize>,
+    /// The match last jumped to.
+    current_match: Option<usize>,
+    /// Only messages matching it are shown in the log.
+    filter: Option<(String, Query)>,
+    /// Messages shown in the log as of the last draw, oldest first.
+    shown: Vec<usize>,
+    /// Messages previously sent to the current URL.
+    history: History,
+    /// The history entry recalled with Up/Down, if any.
+    history_position: Option<usize>,
+    /// What was being typed before recalling history entries.
+    draft: String,
+    /// The entry found by the history search.
+    history_match: Option<usize>,
+    /// Whether the message should be composed in an external editor after this event.
+    open_editor: bool,
+    /// The transcript given with `--replay`.
+    recording: Option<Arc<Recording>>,
+    replay_timing: ReplayTiming,
+    /// The replay in progress or last run, shown next to the log until closed.
+    replay: Option<Replay>,
 }
 
-#[derive(Debug, Default)]
-enum InputField {
-    Url,
+/// A line typed in place of a message to act on the message log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    Search,
+    Filter,
+    /// Looks for a previously sent message.
+    History,
+    /// Asks where to export the message log.
+    Export,
+}
 
+/// What the inspector pane shows of the selected message's payload.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum InspectorTab {
     #[default]
-    Message,
+    Raw,
+    Json,
+    Hex,
 }
 
-impl InputField {
-    fn other(&self) -> Self {
+impl InspectorTab {
+    fn next(self) -> Self {
         match self {
-            InputField::Url => InputField::Message,
-            InputField::Message => InputField::Url,
+            InspectorTab::Raw => InspectorTab::Json,
+            InspectorTab::Json => InspectorTab::Hex,
+            InspectorTab::Hex => InspectorTab::Raw,
         }
     }
-}
 
-#[derive(Debug, Clone)]
-struct ChatMessage {
-    author: Author,
-    content: String,
+    fn name(self) -> &'static str {
+        match self {
+            InspectorTab::Raw => "Raw",
+            InspectorTab::Json => "JSON",
+            InspectorTab::Hex => "Hex",
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Author {
-    User,
-    Origin,
-}
+#[derive(Debug, Default, PartialEq, Eq)]
+enum InputField {
+    Url,
+    Headers,
+    Protocols,
 
-async fn stream(mut stream: SplitStream<WS>, chan: mpsc::Sender<String>) {
-    while let Some(Ok(m)) = stream.next().await {
-        let Some(m) = m.as_text() else { continue };
-        chan.send(m.to_string()).expect("channel should be open");
-    }
+    #[default]
+    Message,
 }
 
-async fn connect(url: String) -> Option<(SplitSink<WS, Message>, SplitStream<WS>)> {
-    let Ok(uri) = Uri::from_str(&url) else {
-        return None;
-    };
-    let Ok((client, _)) = ClientBuilder::from_uri(uri).connect().await else {
-        return None;
-    };
-
-    Some(client.split())
+impl InputField {
+    fn next(&self) -> Self {
+        match self {
+            InputField::Url => InputField::Headers,
+            InputField::Headers => InputField::Protocols,
+            InputField::Protocols => InputField::Message,
+            InputField::Message => InputField::Url,
+        }
+    }
 }
 
 impl App {
-    pub fn new(url: String) -> Self {
+    pub fn new(
+        options: ConnectOptions,
+        session_options: SessionOptions,
+        timestamp_format: TimestampFormat,
+        transcript: Option<Transcript>,
+        recording: Option<Recording>,
+        replay_timing: ReplayTiming,
+    ) -> Self {
         let (sender, receiver) = mpsc::channel();
+        let history = History::load(&options.url);
 
         let messages = Arc::new(SyncMutex::new(Vec::new()));
         let messages_ref = Arc::clone(&messages);
+        let transcript = transcript.map(|t| Arc::new(SyncMutex::new(t)));
+        let transcript_ref = transcript.clone();
 
         thread::spawn(move || {
             for m in receiver {
-                messages_ref.lock().unwrap().push(ChatMessage {
-                    author: Author::Origin,
-                    content: m,
-                });
+                if let Some(transcript) = &transcript_ref {
+                    transcript.lock().unwrap().write(&m);
+                }
+                // Sent messages are only forwarded once sent, possibly after their reply.
+                insert_chronologically(&mut messages_ref.lock().unwrap(), m);
             }
         });
 
         App {
-            sink: Arc::new(Mutex::new(None)),
+            session: Session::new(sender, session_options),
             running: true,
-            sender,
             messages,
-            text_input_content: String::new(),
-            url_content: url,
+            transcript,
+            text_input_content: LineEditor::default(),
+            url_content: LineEditor::new(options.url),
+            headers: options.headers,
+            header_input_content: LineEditor::default(),
+            protocols: options.protocols,
+            tls: options.tls,
+            proxy: options.proxy,
+            resolve: options.resolve,
+            protocol_input_content: LineEditor::default(),
+            show_connection_info: true,
+            show_tls: false,
+            help_scroll: None,
+            binary_view: BinaryView::default(),
+            timestamp_format,
             input_field: InputField::Message,
-            error_while_sending: false,
+            send_error: None,
+            invalid_entry: false,
+            scroll: None,
+            unseen_from: 0,
+            view_height: 0,
+            total_lines: 0,
+            last_visible: None,
+            selected: None,
+            reveal: None,
+            inspector_tab: InspectorTab::default(),
+            inspector_scroll: 0,
+            prompt: None,
+            prompt_input: LineEditor::default(),
+            prompt_error: None,
+            search: None,
+            search_matches: Vec::new(),
+            current_match: None,
+            filter: None,
+            shown: Vec::new(),
+            history,
+            history_position: None,
+            draft: String::new(),
+            history_match: None,
+            open_editor: false,
+            recording: recording.map(Arc::new),
+            replay_timing,
+            replay: None,
         }
     }
 
     pub async fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
         self.running = true;
 
-        {
-            let sink = Arc::clone(&self.sink);
-            let sender = self.sender.clone();
-            let url = self.url_content.clone();
-
-            if !self.url_content.is_empty() {
-                tokio::spawn(async move {
-                    let Some((new_sink, st)) = connect(url).await else {
-                        return;
-                    };
-                    tokio::spawn(stream(st, sender));
-                    let mut s = sink.lock().await;
-
-                    *s = Some(new_sink);
-                });
-            }
+        if !self.url_content.is_empty() {
+            self.reconnect();
         }
 
         while self.running {
             terminal.draw(|frame| self.draw(frame))?;
             self.handle_crossterm_events().await?;
+
+            if std::mem::take(&mut self.open_editor) {
+                self.compose_externally(&mut terminal).await?;
+            }
         }
         Ok(())
     }
@@ -148,21 +325,49 @@ impl App {
     fn draw(&mut self, frame: &mut Frame) {
         let title = Line::from(" RSockTUI ").bold().blue().centered();
         let text = "\n\
-            Press `Esc` or `Ctrl-C` to stop running.\n Press `TAB` to switch from URL setting to chatting.\n Press `Ctrl-R` to reset connection (uses current URL).";
+            Press `Esc` or `Ctrl-C` to stop running.\n \
+            Press `TAB` to cycle between URL, headers, subprotocols and chatting.\n \
+            Press `F1` to show the other keys.";
+
+        let input_height = match self.input_field {
+            _ if self.prompt.is_some() => 3,
+            // Grows with the message, up to a point.
+            InputField::Message => {
+                (self.text_input_content.text().split('\n').count() as u16 + 2).clamp(3, 12)
+            }
+            // One line per header, one for the header being typed, plus the borders.
+            InputField::Url | InputField::Headers | InputField::Protocols => {
+                (self.headers.len().max(self.protocols.len()) as u16 + 3).max(3)
+            }
+        };
 
         let vertical = Layout::vertical([
             Constraint::Length(6),
             Constraint::Min(3),
             Constraint::Length(1),
-            Constraint::Length(3),
+            Constraint::Length(1),
+            Constraint::Length(input_height),
         ]);
 
-        let [prelude_area, messages_area, input_area_name, input_area] =
+        let [prelude_area, messages_area, status_area, input_area_name, input_area] =
             vertical.areas(frame.area());
 
         let horizontal = Layout::horizontal([Constraint::Min(3), Constraint::Length(35)]);
         let [input_area_name, input_error_area] = horizontal.areas(input_area_name);
 
+        let selected = self
+            .selected
+            .filter(|&i| i < self.messages.lock().unwrap().len());
+        let side_panel = match (selected, &self.replay, self.show_connection_info) {
+            (Some(_), _, _) => Constraint::Percentage(50),
+            (None, Some(_), _) => Constraint::Percentage(60),
+            (None, None, true) => Constraint::Length(45),
+            (None, None, false) => Constraint::Length(0),
+        };
+        let body_area = messages_area;
+        let horizontal = Layout::horizontal([Constraint::Min(3), side_panel]);
+        let [messages_area, info_area] = horizontal.areas(messages_area);
+
         frame.render_widget(
             Paragraph::new(text)
                 .block(Block::bordered().title(title))
@@ -170,86 +375,628 @@ impl App {
             prelude_area,
         );
 
-        let (mut messages, lines): (Vec<_>, usize) = {
+        let width = (messages_area.width as usize).saturating_sub(2); // Inside the borders.
+        let (messages, owners, count) = {
             let messages = self.messages.lock().unwrap();
-            let lines = count_lines(messages.iter().map(|m| &m.content));
-            let messages = messages
-                .iter()
-                .cloned()
-                .map(|m| {
-                    let lines = m
-                        .content
-                        .lines()
-                        .map(|s| s.to_string())
-                        .enumerate()
-                        .collect::<Vec<_>>();
-
-                    match m.author {
-                        Author::User => lines
-                            .into_iter()
-                            .map(|(i, s)| {
-                                if i == 0 {
-                                    Text::raw("USER: ".to_string() + &s)
-                                        .fg(ratatui::style::Color::Cyan)
-                                } else {
-                                    Text::raw(s).fg(ratatui::style::Color::Cyan)
-                                }
-                            })
-                            .collect::<Vec<_>>(),
-                        Author::Origin => lines
-                            .into_iter()
-                            .map(|(i, s)| {
-                                if i == 0 {
-                                    Text::raw("ORIG: ".to_string() + &s)
-                                        .fg(ratatui::style::Color::LightYellow)
-                                } else {
-                                    Text::raw(s).fg(ratatui::style::Color::LightYellow)
-                                }
-                            })
-                            .collect::<Vec<_>>(),
+            let opened_at = self.session.status.lock().unwrap().opened_at;
+            let mut rows = Vec::new();
+            // Which message each row belongs to.
+            let mut owners = Vec::new();
+            self.search_matches.clear();
+            self.shown.clear();
+
+            for (i, m) in messages.iter().enumerate() {
+                let text = m.render(self.binary_view);
+                if let Some((_, filter)) = &self.filter {
+                    if !filter.matches(m, &text) {
+                        continue;
                     }
-                })
-                .flatten()
-                .map(|l| ListItem::new(l))
-                .collect();
-            (messages, lines)
+                }
+                self.shown.push(i);
+
+                let search = self.search.as_ref().map(|(_, search)| search);
+                if search.is_some_and(|search| search.matches(m, &text)) {
+                    self.search_matches.push(i);
+                }
+                let highlight = if self.current_match == Some(i) {
+                    Color::LightRed
+                } else {
+                    Color::Yellow
+                };
+
+                let previous = i.checked_sub(1).map(|i| &messages[i].timestamp);
+                let timestamp = self
+                    .timestamp_format
+                    .format(&m.timestamp, previous, opened_at)
+                    .map(|t| t + " ")
+                    .unwrap_or_default();
+                let (prefix, color) = match m.author {
+                    Author::User => ("USER: ", Color::Cyan),
+                    Author::Origin => ("ORIG: ", Color::LightYellow),
+                    Author::System => ("SYS:  ", Color::Gray),
+                };
+                // The first row is shared with the timestamp and the author.
+                let first_width = width
+                    .saturating_sub(timestamp.width() + prefix.width())
+                    .max(1);
+
+                for (j, mut line) in self.body(m, color).into_iter().enumerate() {
+                    if let Some(search) = search {
+                        line = highlight_matches(line, search, highlight);
+                    }
+                    let wrapped = if j == 0 {
+                        wrap(line, first_width, width)
+                    } else {
+                        wrap(line, width, width)
+                    };
+                    for (k, mut row) in wrapped.into_iter().enumerate() {
+                        if j == 0 && k == 0 {
+                            row.splice(0..0, [timestamp.clone().dark_gray(), prefix.fg(color)]);
+                        }
+                        let mut item = ListItem::new(Line::from(row));
+                        if selected == Some(i) {
+                            item = item.bg(Color::Indexed(237));
+                        }
+                        rows.push(item);
+                        owners.push(i);
+                    }
+                }
+            }
+            (rows, owners, messages.len())
         };
+        let shown = self.shown.len();
+        let lines = messages.len();
 
-        let height = messages_area.height - 2; // 2 Seems to be the offset of the border.
-        let messages: Vec<_> = messages
-            .drain(lines.saturating_sub(height as usize)..)
-            .collect();
-        let messages = List::new(messages).block(Block::bordered());
+        let height = messages_area.height as usize - 2; // 2 Seems to be the offset of the border.
+        self.view_height = height;
+        self.total_lines = lines;
+
+        let bottom = lines.saturating_sub(height);
+        if let Some(target) = self.reveal.take() {
+            let first = owners.iter().position(|&i| i == target).unwrap_or(0);
+            let last = owners.iter().rposition(|&i| i == target).unwrap_or(0);
+            let top = self.scroll.map_or(bottom, |top| top.min(bottom));
+
+            if first < top {
+                self.scroll_to(first);
+            } else if last >= top + height {
+                self.scroll_to((last + 1).saturating_sub(height).min(first));
+            }
+        }
+        let top = self.scroll.map_or(bottom, |top| top.min(bottom));
+        let messages: Vec<_> = messages.into_iter().skip(top).take(height).collect();
+        self.last_visible = owners
+            .get((top + height).min(lines).wrapping_sub(1))
+            .copied();
+
+        let mut block = Block::bordered();
+        if let Some((filter, _)) = &self.filter {
+            let title = format!(" Filter: {filter} ({shown} of {count} shown) ");
+            block = block.title(Line::from(title).magenta().bold());
+        }
+        if let Some((search, _)) = &self.search {
+            let position = self
+                .current_match
+                .and_then(|i| self.search_matches.iter().position(|&m| m == i))
+                .map(|p| format!("{}/", p + 1))
+                .unwrap_or_default();
+            let title = format!(
+                " Search: {search} ({position}{} matches) ",
+                self.search_matches.len()
+            );
+            block = block.title(Line::from(title).yellow().bold().right_aligned());
+        }
+        if self.scroll.is_some() {
+            // Only the messages the filter lets through are worth announcing.
+            let unseen = shown - self.shown.partition_point(|&i| i < self.unseen_from);
+            let indicator = match unseen {
+                0 => " Scrolled up, `Ctrl-End` to follow ".to_string(),
+                1 => " ▼ 1 new message below ".to_string(),
+                n => format!(" ▼ {n} new messages below "),
+            };
+            block = block.title_bottom(Line::from(indicator).yellow().bold().right_aligned());
+        }
+        let messages = List::new(messages).block(block);
 
         frame.render_widget(messages, messages_area);
 
-        match self.input_field {
-            InputField::Message => {
-                frame.render_widget(Paragraph::new("Chat Message"), input_area_name);
-                if self.error_while_sending {
+        frame.render_widget(Paragraph::new(self.status_line()), status_area);
+
+        if let Some(selected) = selected {
+            let title = format!(" Message {} of {count} ", selected + 1);
+            frame.render_widget(
+                Paragraph::new(self.inspector(selected))
+                    .wrap(Wrap { trim: false })
+                    .scroll((self.inspector_scroll, 0))
+                    .block(Block::bordered().title(title).title_bottom(
+                        Line::from(" F7 tab, Shift-Up/Down scroll, F6 close ").right_aligned(),
+                    )),
+                info_area,
+            );
+        } else if let Some(replay) = &self.replay {
+            self.draw_replay(frame, replay, info_area);
+        } else if self.show_connection_info {
+            frame.render_widget(
+                Paragraph::new(self.connection_info())
+                    .wrap(Wrap { trim: false })
+                    .block(Block::bordered().title(" Connection ")),
+                info_area,
+            );
+        }
+
+        if self.show_tls {
+            let [popup_area] = Layout::horizontal([Constraint::Max(100)])
+                .flex(Flex::Center)
+                .areas(body_area);
+            frame.render_widget(Clear, popup_area);
+            frame.render_widget(
+                Paragraph::new(self.tls_details())
+                    .wrap(Wrap { trim: false })
+                    .block(
+                        Block::bordered()
+                            .title(" TLS ")
+                            .title_bottom(Line::from(" Ctrl-T or Esc close ").right_aligned()),
+                    ),
+                popup_area,
+            );
+        }
+
+        if let Some(scroll) = self.help_scroll {
+            let help = help();
+            // The prelude is covered too, to make room for as many keys as possible.
+            let [popup_area] = Layout::horizontal([Constraint::Max(80)])
+                .flex(Flex::Center)
+                .areas(prelude_area.union(body_area));
+            let [popup_area] = Layout::vertical([Constraint::Max(help.height() as u16 + 2)])
+                .flex(Flex::Center)
+                .areas(popup_area);
+            frame.render_widget(Clear, popup_area);
+            frame.render_widget(
+                Paragraph::new(help)
+                    .wrap(Wrap { trim: false })
+                    .scroll((scroll, 0))
+                    .block(Block::bordered().title(" Keys ").title_bottom(
+                        Line::from(" Up/Down scroll, F1 or Esc close ").right_aligned(),
+                    )),
+                popup_area,
+            );
+        }
+
+        if let Some(prompt) = self.prompt {
+            match prompt {
+                Prompt::History => {
+                    let title = " Matching: ";
+                    let (_, column) = self.prompt_input.cursor_position();
+                    frame.set_cursor_position((
+                        input_area.x + 1 + (title.width() + column) as u16,
+                        input_area.y,
+                    ));
+                    let found = self
+                        .history_match
+                        .map_or("", |i| &self.history.entries()[i]);
                     frame.render_widget(
-                        Paragraph::new(
-                            "ERROR SENDING MESSAGE! Verify URL.".fg(ratatui::style::Color::Red),
+                        Paragraph::new(Text::raw(found)).block(
+                            Block::bordered()
+                                .title(format!("{title}{} ", self.prompt_input.text())),
                         ),
-                        input_error_area,
+                        input_area,
                     );
                 }
+                Prompt::Search | Prompt::Filter | Prompt::Export => render_editor(
+                    frame,
+                    &self.prompt_input,
+                    Style::new(),
+                    Block::bordered(),
+                    input_area,
+                    true,
+                ),
+            }
+            let name = match prompt {
+                Prompt::Search => {
+                    "Search (Up/Down to jump between matches, Enter to keep, Esc to clear)"
+                }
+                Prompt::Filter => {
+                    "Filter (text, /regex/, author:ORIG, $.type == \"tick\"; empty to clear)"
+                }
+                Prompt::History => {
+                    "History search (Ctrl-F for older matches, Enter to use, Esc to cancel)"
+                }
+                Prompt::Export => {
+                    "Export the message log as JSON Lines to (Enter to save, Esc to cancel)"
+                }
+            };
+            frame.render_widget(Paragraph::new(name), input_area_name);
+            if let Some(error) = &self.prompt_error {
                 frame.render_widget(
-                    Paragraph::new(Text::raw(&self.text_input_content)).block(Block::bordered()),
+                    Paragraph::new(error.clone().fg(Color::Red)),
+                    input_error_area,
+                );
+            }
+            return;
+        }
+
+        match self.input_field {
+            InputField::Message => {
+                frame.render_widget(Paragraph::new("Chat Message"), input_area_name);
+                if let Some(error) = self.send_error {
+                    frame.render_widget(Paragraph::new(error.fg(Color::Red)), input_error_area);
+                }
+                render_editor(
+                    frame,
+                    &self.text_input_content,
+                    Style::new(),
+                    Block::bordered(),
                     input_area,
+                    true,
                 );
             }
-            InputField::Url => {
-                frame.render_widget(Paragraph::new("WS URL"), input_area_name);
+            InputField::Url | InputField::Headers | InputField::Protocols => {
+                let horizontal = Layout::horizontal([
+                    Constraint::Percentage(40),
+                    Constraint::Percentage(35),
+                    Constraint::Percentage(25),
+                ]);
+                let [url_area, headers_area, protocols_area] = horizontal.areas(input_area);
+
+                let block = |title: &'static str, field: InputField| {
+                    let block = Block::bordered().title(title);
+                    if self.input_field == field {
+                        block.bold()
+                    } else {
+                        block
+                    }
+                };
+
+                let (name, error) = match self.input_field {
+                    InputField::Headers => (
+                        "Headers (`Enter` adds, `Backspace` removes)",
+                        "INVALID HEADER! Use `Name: value`.",
+                    ),
+                    InputField::Protocols => (
+                        "Subprotocols (`Enter` adds, `Backspace` removes)",
+                        "INVALID SUBPROTOCOL NAME!",
+                    ),
+                    _ => ("WS URL", ""),
+                };
+                frame.render_widget(Paragraph::new(name), input_area_name);
+
+                if self.invalid_entry {
+                    frame.render_widget(Paragraph::new(error.fg(Color::Red)), input_error_area);
+                }
+
+                render_editor(
+                    frame,
+                    &self.url_content,
+                    Style::new().fg(Color::Rgb(255, 165, 0)),
+                    block(" WS URL ", InputField::Url),
+                    url_area,
+                    self.input_field == InputField::Url,
+                );
+
+                let mut headers: Vec<Line> = self
+                    .headers
+                    .iter()
+                    .map(|h| Line::raw(h.to_string()).fg(Color::Magenta))
+                    .collect();
+                headers.push(Line::raw(format!("> {}", self.header_input_content.text())));
                 frame.render_widget(
-                    Paragraph::new(
-                        Text::raw(&self.url_content).fg(ratatui::style::Color::Rgb(255, 165, 0)),
-                    )
-                    .block(Block::bordered()),
-                    input_area,
+                    Paragraph::new(Text::from(headers))
+                        .block(block(" Headers ", InputField::Headers)),
+                    headers_area,
+                );
+
+                let mut protocols: Vec<Line> = self
+                    .protocols
+                    .iter()
+                    .map(|p| Line::raw(p.as_str()).fg(Color::Green))
+                    .collect();
+                protocols.push(Line::raw(format!(
+                    "> {}",
+                    self.protocol_input_content.text()
+                )));
+                frame.render_widget(
+                    Paragraph::new(Text::from(protocols))
+                        .block(block(" Subprotocols ", InputField::Protocols)),
+                    protocols_area,
                 );
+
+                // The entry being typed comes after the ones already added.
+                let list_cursor = |area: Rect, entries: usize, editor: &LineEditor| {
+                    let (_, column) = editor.cursor_position();
+                    let x = (area.x + 3 + column as u16).min(area.right().saturating_sub(2));
+                    (x, area.y + 1 + entries as u16)
+                };
+                match self.input_field {
+                    InputField::Headers => frame.set_cursor_position(list_cursor(
+                        headers_area,
+                        self.headers.len(),
+                        &self.header_input_content,
+                    )),
+                    InputField::Protocols => frame.set_cursor_position(list_cursor(
+                        protocols_area,
+                        self.protocols.len(),
+                        &self.protocol_input_content,
+                    )),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn status_line(&self) -> Line<'static> {
+        let status = self.session.status.lock().unwrap();
+        let state = status.state;
+        let color = match state {
+            ConnectionState::Open => Color::Green,
+            ConnectionState::Connecting | ConnectionState::Closing => Color::Yellow,
+            ConnectionState::Closed => Color::Red,
+        };
+
+        let mut spans = vec![format!(" ● {state} ").bold().fg(color)];
+        if !self.url_content.is_empty() {
+            spans.push(format!(" {}", self.url_content.text()).into());
+        }
+        if let Some(Ok(handshake)) = self.session.report.lock().unwrap().as_ref() {
+            match (handshake.peer_addr, &handshake.proxy) {
+                (Some(addr), Some(_)) => spans.push(format!(" │ via proxy {addr}").into()),
+                (Some(addr), None) => spans.push(format!(" │ peer {addr}").into()),
+                (None, _) => {}
+            }
+            if let Some(protocol) = &handshake.protocol {
+                spans.push(format!(" │ subprotocol: {protocol}").into());
             }
         }
+        if let (ConnectionState::Open, Some(last_traffic)) = (state, status.last_traffic) {
+            spans.push(
+                format!(
+                    " │ last traffic {:.1}s ago",
+                    last_traffic.elapsed().as_secs_f64()
+                )
+                .into(),
+            );
+        }
+        if let (ConnectionState::Open, Some(rtt)) = (state, status.last_rtt) {
+            spans.push(format!(" │ ping {:.1} ms", rtt.as_secs_f64() * 1000.0).into());
+        }
+        if self.tls.insecure {
+            spans.push(" │ ".into());
+            spans.push(
+                " TLS VERIFICATION DISABLED (--insecure) "
+                    .bold()
+                    .white()
+                    .on_red(),
+            );
+        }
+        if let Some(transcript) = &self.transcript {
+            let transcript = transcript.lock().unwrap();
+            spans.push(match transcript.error() {
+                Some(e) => format!(" │ LOGGING FAILED: {e}").bold().red(),
+                None => format!(" │ logging to {}", transcript.path().display()).into(),
+            });
+        }
+
+        Line::from(spans)
+    }
+
+    /// Shows the responses received since the replay started next to the recorded ones, pair
+    /// by pair, with those that differ in red and the differing part highlighted.
+    fn draw_replay(&self, frame: &mut Frame, replay: &Replay, area: Rect) {
+        let live: Vec<_> = self
+            .messages
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|m| m.author == Author::Origin && m.timestamp.mono >= replay.started)
+            .cloned()
+            .collect();
+        let recorded = &replay.recording.received;
+
+        let block = Block::bordered();
+        let [recorded_area, live_area] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(block.inner(area));
+        let width = (recorded_area.width as usize).saturating_sub(2); // Inside the separator.
+        let (mut left, mut right) = (Vec::new(), Vec::new());
+        let mut matching = 0;
+
+        for i in 0..recorded.len().max(live.len()) {
+            let render = |content: &Content| {
+                ChatMessage::new(Author::Origin, content.clone()).render(self.binary_view)
+            };
+            let (mut recorded_rows, mut live_rows) = match (recorded.get(i), live.get(i)) {
+                (Some(recorded), Some(live)) if same_response(recorded, &live.content) => {
+                    matching += 1;
+                    let rows = |text: &str| diff_rows(text, 0..0, Style::new(), width);
+                    (
+                        rows(&render(recorded)),
+                        rows(&live.render(self.binary_view)),
+                    )
+                }
+                (Some(recorded), Some(live)) => {
+                    let (recorded, live) = (render(recorded), live.render(self.binary_view));
+                    let (recorded_diff, live_diff) = differing_ranges(&recorded, &live);
+                    let style = Style::new().red();
+                    (
+                        diff_rows(&recorded, recorded_diff, style, width),
+                        diff_rows(&live, live_diff, style, width),
+                    )
+                }
+                (Some(recorded), None) => (
+                    diff_rows(&render(recorded), 0..0, Style::new(), width),
+                    vec![Line::raw("(not received yet)").dark_gray()],
+                ),
+                (None, Some(live)) => (
+                    vec![Line::raw("(not recorded)").dark_gray()],
+                    diff_rows(
+                        &live.render(self.binary_view),
+                        0..0,
+                        Style::new().red(),
+                        width,
+                    ),
+                ),
+                (None, None) => unreachable!("one of them is there"),
+            };
+
+            let height = recorded_rows.len().max(live_rows.len());
+            recorded_rows.resize(height, Line::default());
+            live_rows.resize(height, Line::default());
+            left.extend(recorded_rows);
+            left.push(Line::default());
+            right.extend(live_rows);
+            right.push(Line::default());
+        }
+
+        // Follows the latest responses.
+        let scroll = left.len().saturating_sub(recorded_area.height as usize - 1) as u16;
+        let progress = replay.progress.lock().unwrap();
+        let title = match progress.error {
+            Some(e) => format!(" Replay stopped: {e} ").bold().red(),
+            None => format!(
+                " Replay ({}): sent {} of {}, {matching} of {} responses match ",
+                replay.timing.name(),
+                progress.sent,
+                replay.recording.sent.len(),
+                recorded.len()
+            )
+            .into(),
+        };
+
+        frame.render_widget(
+            block
+                .title(title)
+                .title_bottom(Line::from(" F9 close ").right_aligned()),
+            area,
+        );
+        frame.render_widget(
+            Paragraph::new(left).scroll((scroll, 0)).block(
+                Block::new()
+                    .borders(Borders::RIGHT)
+                    .title("Recorded".bold()),
+            ),
+            recorded_area,
+        );
+        frame.render_widget(
+            Paragraph::new(right)
+                .scroll((scroll, 0))
+                .block(Block::new().title(" Live".bold())),
+            live_area,
+        );
+    }
+
+    /// Describes the TLS session of the connection and the server's certificate.
+    fn tls_details(&self) -> Text<'static> {
+        let report = self.session.report.lock().unwrap();
+        let tls = match report.as_ref() {
+            Some(Ok(handshake)) => handshake.tls.as_ref(),
+            _ => return Text::raw("Not connected."),
+        };
+        let Some(tls) = tls else {
+            return Text::raw("Not a TLS connection, use a `wss://` URL.");
+        };
+
+        let mut lines = vec![
+            field(
+                "Version",
+                tls.version.clone().unwrap_or("unknown".to_string()),
+            ),
+            field(
+                "Cipher suite",
+                tls.cipher_suite.clone().unwrap_or("unknown".to_string()),
+            ),
+            field("ALPN", tls.alpn.clone().unwrap_or("none".to_string())),
+            field("Server name (SNI)", tls.server_name.clone()),
+            if tls.verified {
+                field(
+                    "Verification",
+                    "certificate and host name checked".to_string(),
+                )
+            } else {
+                Line::from(vec![
+                    "Verification: ".bold(),
+                    " DISABLED (--insecure) ".bold().white().on_red(),
+                ])
+            },
+        ];
+
+        let Some((leaf, issuers)) = tls.chain.split_first() else {
+            lines.extend([Line::default(), Line::raw("No certificate sent").red()]);
+            return Text::from(lines);
+        };
+        lines.extend([Line::default(), Line::raw("Peer certificate").bold()]);
+        match leaf {
+            Ok(certificate) => lines.extend(leaf_details(certificate, &tls.server_name)),
+            Err(e) => lines.push(Line::raw(format!("Could not be read: {e}")).red()),
+        }
+
+        for (i, certificate) in issuers.iter().enumerate() {
+            lines.extend([
+                Line::default(),
+                Line::raw(format!("Chain certificate {}", i + 1)).bold(),
+            ]);
+            match certificate {
+                Ok(certificate) => lines.extend([
+                    field("Subject", certificate.subject.clone()),
+                    field("Issuer", certificate.issuer.clone()),
+                    Line::from(vec![
+                        "Valid until: ".bold(),
+                        format!("{} (", certificate.not_after).into(),
+                        validity(certificate),
+                        ")".into(),
+                    ]),
+                    field("SHA-256", certificate.fingerprint.clone()),
+                ]),
+                Err(e) => lines.push(Line::raw(format!("Could not be read: {e}")).red()),
+            }
+        }
+
+        Text::from(lines)
+    }
+
+    /// Describes the outcome of the last connection attempt: either the handshake response or
+    /// the reason why it failed.
+    fn connection_info(&self) -> Text<'static> {
+        let report = self.session.report.lock().unwrap();
+
+        let handshake = match report.as_ref() {
+            None if self.url_content.is_empty() => return Text::raw("Not connected."),
+            None => return Text::raw("Connecting..."),
+            Some(Err(e)) => {
+                return Text::from(vec![
+                    Line::raw("Connection failed").bold().red(),
+                    Line::raw(e.to_string()).red(),
+                ])
+            }
+            Some(Ok(handshake)) => handshake,
+        };
+
+        let reason = handshake.status.canonical_reason().unwrap_or("");
+        let mut lines = vec![
+            Line::raw(format!("HTTP {} {}", handshake.status.as_u16(), reason))
+                .bold()
+                .green(),
+            Line::raw(format!(
+                "Subprotocol: {}",
+                handshake.protocol.as_deref().unwrap_or("none")
+            )),
+            Line::raw(format!(
+                "Extensions: {}",
+                handshake.extensions.as_deref().unwrap_or("none")
+            )),
+            Line::raw(format!(
+                "Proxy: {}",
+                handshake.proxy.as_deref().unwrap_or("none")
+            )),
+            Line::raw(""),
+            Line::raw("Response headers").bold(),
+        ];
+        lines.extend(
+            handshake
+                .headers
+                .iter()
+                .map(|(name, value)| Line::raw(format!("{name}: {value}"))),
+        );
+
+        Text::from(lines)
     }
 
     /// If your application needs to perform work in between handling events, you can use the
@@ -259,7 +1006,12 @@ impl App {
             match event::read()? {
                 // it's important to check KeyEventKind::Press to avoid handling key release events
                 Event::Key(key) if key.kind == KeyEventKind::Press => self.on_key_event(key).await,
-                Event::Mouse(_) => {}
+                Event::Paste(text) => self.on_paste(&text),
+                Event::Mouse(mouse) => match mouse.kind {
+                    MouseEventKind::ScrollUp => self.scroll_by(-3),
+                    MouseEventKind::ScrollDown => self.scroll_by(3),
+                    _ => {}
+                },
                 Event::Resize(_, _) => {}
                 _ => {}
             }
@@ -270,89 +1022,955 @@ impl App {
     }
 
     async fn on_key_event(&mut self, key: KeyEvent) {
+        if let Some(scroll) = self.help_scroll {
+            self.help_scroll = match (key.modifiers, key.code) {
+                (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => {
+                    self.quit();
+                    None
+                }
+                (_, KeyCode::Esc | KeyCode::F(1)) => None,
+                (_, KeyCode::Up | KeyCode::PageUp) => Some(scroll.saturating_sub(1)),
+                (_, KeyCode::Down | KeyCode::PageDown) => {
+                    Some((scroll + 1).min(help().height() as u16 - 1))
+                }
+                _ => Some(scroll),
+            };
+            return;
+        }
+        if let Some(prompt) = self.prompt {
+            self.on_prompt_key_event(prompt, key);
+            return;
+        }
+
         match (key.modifiers, key.code) {
+            (_, KeyCode::Esc) if self.show_tls => self.show_tls = false,
             (_, KeyCode::Esc)
             | (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
-            (KeyModifiers::CONTROL, KeyCode::Char('r') | KeyCode::Char('R')) => {
-                let sink = Arc::clone(&self.sink);
-                let sender = self.sender.clone();
-                let url = self.url_content.clone();
-
-                tokio::spawn(async move {
-                    let mut s = sink.lock().await;
-                    let Some((new_sink, st)) = connect(url).await else {
-                        *s = None;
-                        return;
-                    };
-                    tokio::spawn(stream(st, sender));
-                    *s = Some(new_sink);
-                });
-
-                self.messages.lock().unwrap().clear();
+            (KeyModifiers::CONTROL, KeyCode::Char('t') | KeyCode::Char('T')) => {
+                self.show_tls = !self.show_tls;
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('r') | KeyCode::Char('R')) => self.reconnect(),
+            (KeyModifiers::CONTROL, KeyCode::Char('p') | KeyCode::Char('P')) => {
+                let timestamp = Timestamp::now();
+                match self.session.ping().await {
+                    Ok(ping) => {
+                        let mut message = ChatMessage::new(Author::User, ping);
+                        message.timestamp = timestamp;
+                        self.record(message);
+                        self.send_error = None;
+                    }
+                    Err(e) => self.send_error = Some(e),
+                }
+            }
+            (_, KeyCode::F(1)) => self.help_scroll = Some(0),
+            (_, KeyCode::F(2)) => self.show_connection_info = !self.show_connection_info,
+            (_, KeyCode::F(3)) => self.binary_view = self.binary_view.next(),
+            (_, KeyCode::F(4)) => self.timestamp_format = self.timestamp_format.next(),
+            (KeyModifiers::SHIFT, KeyCode::F(5)) => self.toggle_all_json(),
+            (_, KeyCode::F(5)) => self.toggle_json(),
+            (_, KeyCode::F(6)) if self.selected.is_some() => self.selected = None,
+            (_, KeyCode::F(6)) => self.select(0),
+            (_, KeyCode::F(7)) => self.inspector_tab = self.inspector_tab.next(),
+            (KeyModifiers::CONTROL, KeyCode::Up) => self.select(-1),
+            (KeyModifiers::CONTROL, KeyCode::Down) => self.select(1),
+            (KeyModifiers::SHIFT, KeyCode::Up) => {
+                self.inspector_scroll = self.inspector_scroll.saturating_sub(1);
+            }
+            (KeyModifiers::SHIFT, KeyCode::Down) => {
+                self.inspector_scroll = self.inspector_scroll.saturating_add(1);
+            }
+            (_, KeyCode::PageUp) => self.scroll_by(-(self.page() as isize)),
+            (_, KeyCode::PageDown) => self.scroll_by(self.page() as isize),
+            (KeyModifiers::CONTROL, KeyCode::Home) => self.scroll_by(-(self.total_lines as isize)),
+            (KeyModifiers::CONTROL, KeyCode::End) => self.follow_tail(),
+            (KeyModifiers::CONTROL, KeyCode::Char('g') | KeyCode::Char('G')) => {
+                self.open_prompt(Prompt::Search);
             }
-            (_, KeyCode::Char(c)) => match self.input_field {
-                InputField::Message => self.text_input_content.push(c),
-                InputField::Url => self.url_content.push(c),
+            (_, KeyCode::F(8)) => self.open_prompt(Prompt::Filter),
+            (KeyModifiers::CONTROL, KeyCode::Char('f') | KeyCode::Char('F'))
+                if self.input_field == InputField::Message =>
+            {
+                self.open_prompt(Prompt::History);
+            }
+            (KeyModifiers::NONE, KeyCode::Up) if self.input_field == InputField::Message => {
+                if !self.text_input_content.move_line(true) {
+                    self.recall(true);
+                }
+            }
+            (KeyModifiers::NONE, KeyCode::Down) if self.input_field == InputField::Message => {
+                if !self.text_input_content.move_line(false) {
+                    self.recall(false);
+                }
+            }
+            (modifiers, KeyCode::Enter)
+                if self.input_field == InputField::Message
+                    && modifiers.intersects(KeyModifiers::SHIFT | KeyModifiers::ALT) =>
+            {
+                self.text_input_content.insert_str("\n");
+                self.on_edit();
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('o') | KeyCode::Char('O'))
+                if self.input_field == InputField::Message =>
+            {
+                self.open_editor = true;
+            }
+            (_, KeyCode::F(9)) => match (&self.replay, &self.recording) {
+                // Dropping the replay stops it.
+                (Some(_), _) => self.replay = None,
+                (None, Some(recording)) => {
+                    self.replay = Some(Replay::start(
+                        Arc::clone(recording),
+                        self.session.clone(),
+                        self.replay_timing,
+                    ));
+                }
+                (None, None) => self.send_error = Some("NO TRANSCRIPT! Start with --replay."),
             },
+            (KeyModifiers::CONTROL, KeyCode::Char('s') | KeyCode::Char('S')) => {
+                self.open_prompt(Prompt::Export);
+            }
+            (KeyModifiers::ALT, KeyCode::Up) => self.jump_to_match(true),
+            (KeyModifiers::ALT, KeyCode::Down) => self.jump_to_match(false),
 
             (_, KeyCode::Enter) => match self.input_field {
                 InputField::Message => {
-                    let mut s = self.sink.lock().await;
-                    if let Some(s) = s.as_mut() {
-                        if let Ok(_) = s.send(Message::text(self.text_input_content.clone())).await
-                        {
-                            self.messages.lock().unwrap().push(ChatMessage {
-                                author: Author::User,
-                                content: self.text_input_content.clone(),
-                            });
-                            self.error_while_sending = false;
-                        } else {
-                            self.error_while_sending = true;
+                    let content = match Content::parse_input(self.text_input_content.text()) {
+                        Ok(content) => content,
+                        Err(e) => {
+                            self.send_error = Some(e);
+                            return;
                         }
-                    } else {
-                        self.error_while_sending = true;
+                    };
+
+                    self.history.push(self.text_input_content.text());
+                    self.history_position = None;
+
+                    let message = ChatMessage::new(Author::User, content);
+                    match self.session.send(&message.content).await {
+                        Ok(()) => {
+                            self.record(message);
+                            self.send_error = None;
+                            self.follow_tail();
+                        }
+                        Err(e) => self.send_error = Some(e),
                     }
 
                     self.text_input_content.clear();
                 }
                 InputField::Url => {
-                    let sink = Arc::clone(&self.sink);
-                    let sender = self.sender.clone();
-                    let url = self.url_content.clone();
-
-                    tokio::spawn(async move {
-                        let mut s = sink.lock().await;
-                        let Some((new_sink, st)) = connect(url).await else {
-                            *s = None;
-                            return;
-                        };
-                        tokio::spawn(stream(st, sender));
-                        *s = Some(new_sink);
-                    });
+                    self.reconnect();
 
-                    self.error_while_sending = false;
+                    self.send_error = None;
                     self.input_field = InputField::Message;
-                    self.messages.lock().unwrap().clear();
                 }
-            },
-            (_, KeyCode::Tab) => self.input_field = self.input_field.other(),
-            (_, KeyCode::Backspace) => match self.input_field {
-                InputField::Message => {
-                    self.text_input_content.pop();
+                InputField::Headers => {
+                    if self.header_input_content.is_empty() {
+                        return;
+                    }
+
+                    match Header::from_str(self.header_input_content.text()) {
+                        Ok(header) => {
+                            self.headers.push(header);
+                            self.header_input_content.clear();
+                            self.invalid_entry = false;
+                        }
+                        Err(_) => self.invalid_entry = true,
+                    }
                 }
-                InputField::Url => {
-                    self.url_content.pop();
+                InputField::Protocols => {
+                    if self.protocol_input_content.is_empty() {
+                        return;
+                    }
+
+                    match parse_protocol(self.protocol_input_content.text()) {
+                        Ok(protocol) => {
+                            self.protocols.push(protocol);
+                            self.protocol_input_content.clear();
+                            self.invalid_entry = false;
+                        }
+                        Err(_) => self.invalid_entry = true,
+                    }
                 }
             },
-            _ => {}
+            (_, KeyCode::Tab) => self.input_field = self.input_field.next(),
+            // Erasing past the start of the input removes the last entry instead.
+            (_, KeyCode::Backspace)
+                if self.input_field == InputField::Headers
+                    && self.header_input_content.is_empty() =>
+            {
+                self.headers.pop();
+                self.invalid_entry = false;
+            }
+            (_, KeyCode::Backspace)
+                if self.input_field == InputField::Protocols
+                    && self.protocol_input_content.is_empty() =>
+            {
+                self.protocols.pop();
+                self.invalid_entry = false;
+            }
+            _ => {
+                let editor = self.editor();
+                let before = editor.text().to_string();
+                if editor.on_key(key) && editor.text() != before {
+                    self.on_edit();
+                }
+            }
+        }
+    }
+
+    /// The input being typed in.
+    fn editor(&mut self) -> &mut LineEditor {
+        match self.input_field {
+            InputField::Message => &mut self.text_input_content,
+            InputField::Url => &mut self.url_content,
+            InputField::Headers => &mut self.header_input_content,
+            InputField::Protocols => &mut self.protocol_input_content,
+        }
+    }
+
+    fn on_edit(&mut self) {
+        if self.input_field == InputField::Message {
+            self.history_position = None;
+        }
+        self.invalid_entry = false;
+    }
+
+    /// Inserts pasted text in the input being typed in. Only messages can span several lines.
+    fn on_paste(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        let multiline = self.prompt.is_none() && self.input_field == InputField::Message;
+        let text = if multiline {
+            text
+        } else {
+            text.replace('\n', " ")
+        };
+
+        match self.prompt {
+            Some(prompt) => {
+                self.prompt_input.insert_str(&text);
+                self.on_prompt_edit(prompt);
+            }
+            None => {
+                self.editor().insert_str(&text);
+                self.on_edit();
+            }
+        }
+    }
+
+    /// Lets the message be written in `$VISUAL` or `$EDITOR` (`vi` by default), starting from
+    /// the current draft. The TUI is suspended while the editor runs.
+    async fn compose_externally(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        let draft = self.text_input_content.text();
+        // Lets the editor highlight JSON.
+        let extension = if json::parse_document(draft).is_some() {
+            "json"
+        } else {
+            "txt"
+        };
+        // Created with a random name, only readable by us, and removed when dropped.
+        let file = tempfile::Builder::new()
+            .prefix("rsocktui-")
+            .suffix(&format!(".{extension}"))
+            .tempfile()
+            .and_then(|mut file| file.write_all(draft.as_bytes()).map(|()| file));
+        let Ok(file) = file else {
+            self.send_error = Some("COULD NOT CREATE A TEMPORARY FILE!");
+            return Ok(());
+        };
+        let path = file.path();
+
+        let editor = ["VISUAL", "EDITOR"]
+            .into_iter()
+            .filter_map(|var| env::var(var).ok())
+            .find(|editor| !editor.trim().is_empty())
+            .unwrap_or_else(|| "vi".to_string());
+        // Editors such as `code --wait` come with arguments.
+        let mut words = editor.split_whitespace();
+        let program = words.next().unwrap_or("vi");
+
+        disable_terminal_features()?;
+        ratatui::restore();
+        let status = tokio::process::Command::new(program)
+            .args(words)
+            .arg(path)
+            .status()
+            .await;
+        crossterm::terminal::enable_raw_mode()?;
+        crossterm::execute!(io::stdout(), EnterAlternateScreen)?;
+        enable_terminal_features()?;
+        terminal.clear()?;
+
+        match status
+            .ok()
+            .filter(|status| status.success())
+            .map(|_| fs::read_to_string(path))
+        {
+            Some(Ok(mut message)) => {
+                // Editors usually end files with a newline, which is not part of the message.
+                if message.ends_with('\n') {
+                    message.pop();
+                }
+                self.text_input_content.set(message);
+                self.on_edit();
+                self.send_error = None;
+            }
+            _ => self.send_error = Some("EDITOR FAILED! Check $VISUAL or $EDITOR."),
+        }
+
+        Ok(())
+    }
+
+    /// Drops the current connection (if any) and connects again to the current URL, sending
+    /// the current headers and offering the current subprotocols in the handshake.
+    ///
+    /// The message log is cleared, since it refers to the previous connection.
+    fn reconnect(&mut self) {
+        self.messages.lock().unwrap().clear();
+        self.follow_tail();
+        self.selected = None;
+        self.current_match = None;
+        self.history = History::load(self.url_content.text());
+        self.history_position = None;
+        if let Some(transcript) = &self.transcript {
+            transcript.lock().unwrap().set_url(self.url_content.text());
         }
+
+        self.session.reconnect(ConnectOptions {
+            url: self.url_content.text().to_string(),
+            headers: self.headers.clone(),
+            protocols: self.protocols.clone(),
+            tls: self.tls.clone(),
+            proxy: self.proxy.clone(),
+            resolve: self.resolve.clone(),
+        });
+    }
+
+    /// Adds a message of ours to the log, and to the transcript if there is one.
+    fn record(&mut self, message: ChatMessage) {
+        if let Some(transcript) = &self.transcript {
+            transcript.lock().unwrap().write(&message);
+        }
+        insert_chronologically(&mut self.messages.lock().unwrap(), message);
     }
 
     fn quit(&mut self) {
         self.running = false;
     }
+
+    /// Lines scrolled by `PageUp` and `PageDown`, keeping one line of context.
+    fn page(&self) -> usize {
+        self.view_height.saturating_sub(1).max(1)
+    }
+
+    /// Scrolls the message log by `delta` lines (up if negative). Scrolling up stops following
+    /// the latest messages, and scrolling back down to them resumes it.
+    fn scroll_by(&mut self, delta: isize) {
+        let bottom = self.total_lines.saturating_sub(self.view_height);
+        self.scroll_to(self.scroll.unwrap_or(bottom).saturating_add_signed(delta));
+    }
+
+    /// Shows the log from line `top`, following the latest messages if they are all in view.
+    fn scroll_to(&mut self, top: usize) {
+        let bottom = self.total_lines.saturating_sub(self.view_height);
+        let top = top.min(bottom);
+
+        if top == bottom {
+            self.follow_tail();
+        } else {
+            if self.scroll.is_none() {
+                self.unseen_from = self.messages.lock().unwrap().len();
+            }
+            self.scroll = Some(top);
+        }
+    }
+
+    fn follow_tail(&mut self) {
+        self.scroll = None;
+    }
+
+    /// The lines of a message, with JSON payloads highlighted.
+    fn body(&self, m: &ChatMessage, color: Color) -> Vec<Vec<Span<'static>>> {
+        if let Some(value) = &m.json {
+            return json::lines(value, m.expanded)
+                .into_iter()
+                .map(|line| {
+                    line.into_iter()
+                        .map(|(token, text)| text.fg(json_color(token, color)))
+                        .collect()
+                })
+                .collect();
+        }
+
+        let content = m.render(self.binary_view);
+        if content.is_empty() {
+            return vec![Vec::new()];
+        }
+        content
+            .lines()
+            .map(|line| vec![line.to_string().fg(color)])
+            .collect()
+    }
+
+    /// The message affected by `F5`: the selected one, or else the newest JSON message in view.
+    fn json_target(&self, messages: &[ChatMessage]) -> Option<usize> {
+        match self.selected {
+            Some(i) => Some(i).filter(|&i| messages.get(i).is_some_and(|m| m.json.is_some())),
+            None => {
+                let last = self.last_visible.map_or(0, |i| i + 1).min(messages.len());
+                messages[..last].iter().rposition(|m| m.json.is_some())
+            }
+        }
+    }
+
+    /// Switches a JSON message between its compact and expanded forms.
+    fn toggle_json(&mut self) {
+        let mut messages = self.messages.lock().unwrap();
+
+        if let Some(i) = self.json_target(&messages) {
+            messages[i].expanded = !messages[i].expanded;
+        }
+    }
+
+    /// Switches every JSON message to the opposite form of the one `F5` would toggle.
+    fn toggle_all_json(&mut self) {
+        let mut messages = self.messages.lock().unwrap();
+        let Some(i) = self.json_target(&messages) else {
+            return;
+        };
+
+        let expanded = !messages[i].expanded;
+        for m in messages.iter_mut() {
+            m.expanded = expanded;
+        }
+    }
+
+    /// Moves the selection by `delta` messages (towards older ones if negative), starting from
+    /// the newest message in view, and scrolls the log to it.
+    fn select(&mut self, delta: isize) {
+        if self.shown.is_empty() {
+            return;
+        }
+
+        // Messages hidden by the filter are skipped.
+        let position = match self.selected.or(self.last_visible) {
+            Some(i) => {
+                let position = self.shown.partition_point(|&shown| shown < i);
+                match self.selected {
+                    Some(_) => position.saturating_add_signed(delta),
+                    None => position,
+                }
+            }
+            None => self.shown.len() - 1,
+        };
+        let selected = self.shown[position.min(self.shown.len() - 1)];
+
+        self.selected = Some(selected);
+        self.reveal = Some(selected);
+        self.inspector_scroll = 0;
+    }
+
+    fn open_prompt(&mut self, prompt: Prompt) {
+        self.prompt_input
+            .set(match (prompt, &self.search, &self.filter) {
+                (Prompt::Search, Some((search, _)), _) => search.clone(),
+                (Prompt::Filter, _, Some((filter, _))) => filter.clone(),
+                (Prompt::Export, _, _) => {
+                    let now = SystemTime::now().duration_since(UNIX_EPOCH);
+                    format!("rsocktui-{}.jsonl", now.unwrap_or_default().as_secs())
+                }
+                _ => String::new(),
+            });
+        self.prompt_error = None;
+        self.history_match = None;
+        self.prompt = Some(prompt);
+    }
+
+    fn on_prompt_key_event(&mut self, prompt: Prompt, key: KeyEvent) {
+        match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('c') | KeyCode::Char('C')) => self.quit(),
+            (_, KeyCode::Esc) => {
+                if prompt == Prompt::Search {
+                    self.search = None;
+                    self.current_match = None;
+                }
+                self.prompt = None;
+            }
+            (KeyModifiers::CONTROL, KeyCode::Char('f') | KeyCode::Char('F'))
+                if prompt == Prompt::History =>
+            {
+                let before = self.history_match;
+                if let Some(older) = self.history.search(self.prompt_input.text(), before) {
+                    self.history_match = Some(older);
+                }
+            }
+            (_, KeyCode::Enter) => match prompt {
+                Prompt::History => {
+                    if let Some(i) = self.history_match {
+                        self.text_input_content
+                            .set(self.history.entries()[i].clone());
+                        self.history_position = None;
+                    }
+                    self.prompt = None;
+                }
+                Prompt::Search => {
+                    if self.current_match.is_none() {
+                        self.jump_to_match(true);
+                    }
+                    self.prompt = None;
+                }
+                Prompt::Filter if self.prompt_input.text().trim().is_empty() => {
+                    self.filter = None;
+                    self.prompt = None;
+                }
+                Prompt::Filter => match Query::from_str(self.prompt_input.text()) {
+                    Ok(query) => {
+                        self.filter = Some((self.prompt_input.text().trim().to_string(), query));
+                        self.prompt = None;
+                        self.follow_tail();
+                    }
+                    Err(e) => self.prompt_error = Some(e),
+                },
+                Prompt::Export => {
+                    let path = self.prompt_input.text().trim().to_string();
+                    if path.is_empty() {
+                        self.prompt_error =
+                            Some("Enter the path of the file to create".to_string());
+                        return;
+                    }
+
+                    let count = {
+                        let messages = self.messages.lock().unwrap();
+                        let exported = transcript::export(
+                            Path::new(&path),
+                            &messages,
+                            self.url_content.text(),
+                        );
+                        if let Err(e) = exported {
+                            self.prompt_error = Some(format!("Could not export to {path}: {e}"));
+                            return;
+                        }
+                        messages.len()
+                    };
+                    self.record(ChatMessage::system(format!(
+                        "Exported {count} messages to {path}"
+                    )));
+                    self.follow_tail();
+                    self.prompt = None;
+                }
+            },
+            (_, KeyCode::Up) if prompt == Prompt::Search => self.jump_to_match(true),
+            (_, KeyCode::Down) if prompt == Prompt::Search => self.jump_to_match(false),
+            _ => {
+                let before = self.prompt_input.text().to_string();
+                if self.prompt_input.on_key(key) && self.prompt_input.text() != before {
+                    self.on_prompt_edit(prompt);
+                }
+            }
+        }
+    }
+
+    /// Searches as the query is typed. Filters only apply once confirmed, since hiding
+    /// messages at every keystroke would be confusing.
+    fn on_prompt_edit(&mut self, prompt: Prompt) {
+        self.prompt_error = None;
+        match prompt {
+            Prompt::Search => {}
+            Prompt::Filter | Prompt::Export => return,
+            Prompt::History => {
+                self.history_match = self.history.search(self.prompt_input.text(), None);
+                return;
+            }
+        }
+
+        self.current_match = None;
+        self.search = if self.prompt_input.is_empty() {
+            None
+        } else {
+            match Query::from_str(self.prompt_input.text()) {
+                Ok(query) => Some((self.prompt_input.text().to_string(), query)),
+                Err(e) => {
+                    self.prompt_error = Some(e);
+                    None
+                }
+            }
+        };
+    }
+
+    /// Replaces the message being typed with the previous (older) or next sent message, going
+    /// back to what was being typed past the newest one.
+    fn recall(&mut self, older: bool) {
+        let len = self.history.entries().len();
+        let position = match (self.history_position, older) {
+            (None, false) => return,
+            (None, true) if len == 0 => return,
+            (None, true) => {
+                self.draft = self.text_input_content.take();
+                len - 1
+            }
+            (Some(i), true) => i.saturating_sub(1),
+            (Some(i), false) if i + 1 < len => i + 1,
+            (Some(_), false) => {
+                self.text_input_content.set(std::mem::take(&mut self.draft));
+                self.history_position = None;
+                return;
+            }
+        };
+
+        self.text_input_content
+            .set(self.history.entries()[position].clone());
+        self.history_position = Some(position);
+    }
+
+    /// Scrolls to the previous (older) or next search match, starting from the newest one.
+    fn jump_to_match(&mut self, older: bool) {
+        let matches = &self.search_matches;
+        let target = match (self.current_match, older) {
+            (None, _) => matches.last(),
+            (Some(current), true) => matches.iter().rev().find(|&&i| i < current),
+            (Some(current), false) => matches.iter().find(|&&i| i > current),
+        };
+
+        if let Some(&target) = target {
+            self.current_match = Some(target);
+            self.reveal = Some(target);
+        }
+    }
+
+    /// Details of the selected message, for the inspector pane.
+    fn inspector(&self, selected: usize) -> Text<'static> {
+        let messages = self.messages.lock().unwrap();
+        let Some(m) = messages.get(selected) else {
+            return Text::default();
+        };
+        let opened_at = self.session.status.lock().unwrap().opened_at;
+        let previous = selected.checked_sub(1).map(|i| &messages[i].timestamp);
+
+        let field =
+            |name: &str, value: String| Line::from(vec![format!("{name}: ").bold(), value.into()]);
+        let since = |format: TimestampFormat| {
+            format
+                .format(&m.timestamp, previous, opened_at)
+                .unwrap_or_default()
+        };
+
+        let author = match m.author {
+            Author::User => "USER (sent)",
+            Author::Origin => "ORIG (received)",
+            Author::System => "SYS (generated by rsocktui)",
+        };
+        let mut lines = vec![
+            field("Author", author.to_string()),
+            field("Time", m.timestamp.format_wall()),
+            field("Since open", since(TimestampFormat::Relative)),
+            field("Since previous", since(TimestampFormat::Delta)),
+        ];
+
+        let payload = m.content.payload();
+        if !matches!(m.author, Author::System) {
+            let (opcode, name) = m.content.opcode();
+            lines.extend([
+                field("Frame", format!("{name} (opcode {opcode:#x})")),
+                field("Length", format!("{} bytes", payload.len())),
+                // tokio-websockets hands over messages with their fragments already joined.
+                field(
+                    "Fragmentation",
+                    "not tracked, fragments arrive reassembled".to_string(),
+                ),
+            ]);
+            match &m.content {
+                Content::Close {
+                    code: Some(code), ..
+                } => {
+                    lines.push(field("Close code", u16::from(*code).to_string()));
+                }
+                Content::Pong { rtt: Some(rtt), .. } => {
+                    let rtt = format!("{:.1} ms", rtt.as_secs_f64() * 1000.0);
+                    lines.push(field("Round trip", rtt));
+                }
+                _ => {}
+            }
+        }
+
+        let tabs = [InspectorTab::Raw, InspectorTab::Json, InspectorTab::Hex]
+            .into_iter()
+            .flat_map(|tab| {
+                let name = format!(" {} ", tab.name());
+                let name = if tab == self.inspector_tab {
+                    name.reversed().bold()
+                } else {
+                    name.dark_gray()
+                };
+                [name, " ".into()]
+            });
+        lines.extend([Line::default(), Line::from_iter(tabs), Line::default()]);
+
+        match self.inspector_tab {
+            InspectorTab::Raw => {
+                // Control characters would be interpreted by the terminal, so they are escaped.
+                let raw: String = String::from_utf8_lossy(&payload)
+                    .chars()
+                    .flat_map(|c| match c {
+                        '\n' | '\t' => vec![c],
+                        c if c.is_control() => c.escape_default().collect(),
+                        c => vec![c],
+                    })
+                    .collect();
+                lines.extend(raw.lines().map(|line| Line::raw(line.to_string())));
+            }
+            InspectorTab::Json => match &m.json {
+                Some(value) => lines.extend(json::lines(value, true).into_iter().map(|line| {
+                    Line::from_iter(
+                        line.into_iter()
+                            .map(|(token, text)| text.fg(json_color(token, Color::Reset))),
+                    )
+                })),
+                None => lines.push("Not a JSON document.".dark_gray().into()),
+            },
+            InspectorTab::Hex if payload.is_empty() => {
+                lines.push("Empty payload.".dark_gray().into());
+            }
+            InspectorTab::Hex => {
+                lines.extend(
+                    hex_dump(&payload)
+                        .lines()
+                        .map(|line| Line::raw(line.to_string())),
+                );
+            }
+        }
+
+        Text::from(lines)
+    }
 }
 
-fn count_lines<'a>(messages: impl Iterator<Item = &'a String>) -> usize {
-    messages.map(|m| m.lines().count()).sum()
+/// Splits `line` into rows that fit in `width` columns (`first_width` for the first one),
+/// measuring characters by their display width. Every row holds at least one character.
+fn wrap(line: Vec<Span<'static>>, first_width: usize, width: usize) -> Vec<Vec<Span<'static>>> {
+    let mut rows = vec![Vec::new()];
+    let mut used = 0;
+    let mut available = first_width;
+
+    for span in line {
+        let mut piece = String::new();
+        for c in span.content.chars() {
+            let w = c.width().unwrap_or(0);
+            if used + w > available && used > 0 {
+                if !piece.is_empty() {
+                    let piece = Span::styled(std::mem::take(&mut piece), span.style);
+                    rows.last_mut().expect("there is always a row").push(piece);
+                }
+                rows.push(Vec::new());
+                used = 0;
+                available = width;
+            }
+            used += w;
+            piece.push(c);
+        }
+        if !piece.is_empty() {
+            let piece = Span::styled(piece, span.style);
+            rows.last_mut().expect("there is always a row").push(piece);
+        }
+    }
+
+    rows
+}
+
+/// Turns on the terminal features the app relies on, besides those set up by `ratatui::init`.
+pub fn enable_terminal_features() -> io::Result<()> {
+    let mut stdout = io::stdout();
+    // Lets the mouse wheel scroll the message log, and pasted text be told apart from typing.
+    crossterm::execute!(stdout, EnableMouseCapture, EnableBracketedPaste)?;
+    // Lets Shift-Enter be told apart from Enter, in the terminals that support it.
+    if supports_keyboard_enhancement().unwrap_or(false) {
+        crossterm::execute!(
+            stdout,
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+        )?;
+    }
+    Ok(())
+}
+
+pub fn disable_terminal_features() -> io::Result<()> {
+    let mut stdout = io::stdout();
+    if supports_keyboard_enhancement().unwrap_or(false) {
+        crossterm::execute!(stdout, PopKeyboardEnhancementFlags)?;
+    }
+    crossterm::execute!(stdout, DisableMouseCapture, DisableBracketedPaste)
+}
+
+/// `text` in rows of at most `width` columns, in `style`, with the part within `highlight`
+/// (a byte range) standing out.
+fn diff_rows(
+    text: &str,
+    highlight: Range<usize>,
+    style: Style,
+    width: usize,
+) -> Vec<Line<'static>> {
+    let mut rows = Vec::new();
+    let mut start = 0;
+
+    for line in text.split('\n') {
+        let end = start + line.len();
+        let from = highlight.start.clamp(start, end) - start;
+        let to = highlight.end.clamp(start, end) - start;
+        let spans = vec![
+            Span::styled(line[..from].to_string(), style),
+            Span::styled(line[from..to].to_string(), style.reversed()),
+            Span::styled(line[to..].to_string(), style),
+        ];
+        rows.extend(wrap(spans, width, width).into_iter().map(Line::from));
+        start = end + 1;
+    }
+
+    rows
+}
+
+/// Renders `editor` in `area`, scrolled so that its cursor is in view, and shows the terminal
+/// cursor there if `focused`.
+fn render_editor(
+    frame: &mut Frame,
+    editor: &LineEditor,
+    style: Style,
+    block: Block,
+    area: Rect,
+    focused: bool,
+) {
+    let inner = block.inner(area);
+    let (line, column) = editor.cursor_position();
+    let x_offset = (column + 1).saturating_sub(inner.width as usize) as u16;
+    let y_offset = (line + 1).saturating_sub(inner.height as usize) as u16;
+
+    frame.render_widget(
+        Paragraph::new(Text::raw(editor.text()).style(style))
+            .block(block)
+            .scroll((y_offset, x_offset)),
+        area,
+    );
+    if focused {
+        frame.set_cursor_position((
+            inner.x + column as u16 - x_offset,
+            inner.y + line as u16 - y_offset,
+        ));
+    }
+}
+
+/// Marks the parts of `line` matching `query` with a `highlight` background.
+fn highlight_matches(
+    line: Vec<Span<'static>>,
+    query: &Query,
+    highlight: Color,
+) -> Vec<Span<'static>> {
+    let text: String = line.iter().map(|span| span.content.as_ref()).collect();
+    let matches = query.find_in(&text);
+    if matches.is_empty() {
+        return line;
+    }
+
+    let mut highlighted = Vec::new();
+    let mut offset = 0;
+    for span in line {
+        let range = offset..offset + span.content.len();
+        offset = range.end;
+
+        // Split points of the span, relative to the start of the line.
+        let mut cuts = vec![range.start, range.end];
+        for m in &matches {
+            cuts.extend([m.start, m.end].into_iter().filter(|c| range.contains(c)));
+        }
+        cuts.sort_unstable();
+        cuts.dedup();
+
+        for piece in cuts.windows(2) {
+            let (start, end) = (piece[0], piece[1]);
+            let text = span.content[start - range.start..end - range.start].to_string();
+            let mut piece = Span::styled(text, span.style);
+            if matches.iter().any(|m| m.start <= start && end <= m.end) {
+                piece = piece.bg(highlight).fg(Color::Black);
+            }
+            highlighted.push(piece);
+        }
+    }
+
+    highlighted
+}
+
+fn field(name: &str, value: String) -> Line<'static> {
+    Line::from(vec![format!("{name}: ").bold(), value.into()])
+}
+
+/// Whether `certificate` is valid now, and for how long.
+fn validity(certificate: &PeerCertificate) -> Span<'static> {
+    let now = SystemTime::now();
+    let days = |duration: Duration| duration.as_secs() / 86_400;
+
+    if certificate.valid_from() > now {
+        return "NOT YET VALID".bold().red();
+    }
+    match certificate.valid_until().duration_since(now) {
+        Ok(left) if days(left) < 14 => format!("expires in {} days", days(left)).yellow(),
+        Ok(left) => format!("expires in {} days", days(left)).green(),
+        Err(e) => format!("EXPIRED {} days ago", days(e.duration()))
+            .bold()
+            .red(),
+    }
+}
+
+/// Everything about the server's own certificate, checked against `server_name`.
+fn leaf_details(certificate: &PeerCertificate, server_name: &str) -> Vec<Line<'static>> {
+    let host_match = if certificate.matches_host(server_name) {
+        "yes".green()
+    } else {
+        format!("NO, `{server_name}` is not in the SAN list")
+            .bold()
+            .red()
+    };
+    let alt_names = match certificate.alt_names.as_slice() {
+        [] => "none".to_string(),
+        names => names.join(", "),
+    };
+
+    vec![
+        field("Subject", certificate.subject.clone()),
+        field("Issuer", certificate.issuer.clone()),
+        field("SAN", alt_names),
+        Line::from(vec!["Matches server name: ".bold(), host_match]),
+        field("Valid from", certificate.not_before.clone()),
+        Line::from(vec![
+            "Valid until: ".bold(),
+            format!("{} (", certificate.not_after).into(),
+            validity(certificate),
+            ")".into(),
+        ]),
+        field("Serial", certificate.serial.clone()),
+        field("Signature", certificate.signature_algorithm.clone()),
+        field("Public key", certificate.key_algorithm.clone()),
+        field("SHA-256", certificate.fingerprint.clone()),
+    ]
+}
+
+/// The key bindings, along with how to send binary and control frames.
+fn help() -> Text<'static> {
+    let mut lines: Vec<_> = KEY_BINDINGS
+        .iter()
+        .map(|&(keys, action)| Line::from(vec![format!(" {keys:<18}").bold(), action.into()]))
+        .collect();
+    lines.extend([
+        Line::default(),
+        Line::raw(" Prefix a message with `:hex ` or `:b64 ` to send it as a binary frame, or"),
+        Line::raw(" with `:text ` to send the rest as is, e.g. when it starts with `:`."),
+        Line::raw(" Send control frames with `:ping [payload]`, `:pong [payload]` and"),
+        Line::raw(" `:close [code] [reason]`."),
+    ]);
+
+    Text::from(lines)
+}
+
+/// The color of each kind of JSON token, punctuation keeping the author's color.
+fn json_color(token: Token, color: Color) -> Color {
+    match token {
+        Token::Key => Color::LightBlue,
+        Token::String => Color::Green,
+        Token::Number => Color::LightMagenta,
+        Token::Bool | Token::Null => Color::LightRed,
+        Token::Punctuation => color,
+    }
 }
@@ -0,0 +1,206 @@
+use std::{
+    fmt::Write,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::SystemTime,
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use sha2::{Digest, Sha256};
+use x509_parser::{
+    certificate::X509Certificate,
+    extensions::GeneralName,
+    objects::{oid2sn, oid_registry},
+    oid_registry::Oid,
+    prelude::FromDer,
+};
+
+/// What is worth knowing about a server certificate when debugging a connection.
+#[derive(Debug, Clone)]
+pub struct PeerCertificate {
+    pub subject: String,
+    pub issuer: String,
+    /// Subject alternative names, as `DNS:example.com`, `IP:127.0.0.1`...
+    pub alt_names: Vec<String>,
+    /// Start and end of validity, in RFC 3339.
+    pub not_before: String,
+    pub not_after: String,
+    pub serial: String,
+    pub signature_algorithm: String,
+    /// Algorithm of the public key, along with its size.
+    pub key_algorithm: String,
+    /// SHA-256 of the DER encoding, as colon separated hex.
+    pub fingerprint: String,
+    validity: (SystemTime, SystemTime),
+}
+
+impl PeerCertificate {
+    /// Reads the fields of a DER encoded X.509 certificate.
+    pub fn parse(der: &[u8]) -> Result<Self, String> {
+        let (_, certificate) = X509Certificate::from_der(der).map_err(|e| e.to_string())?;
+        let validity = certificate.validity();
+        let (not_before, not_after): (SystemTime, SystemTime) = (
+            validity.not_before.to_datetime().into(),
+            validity.not_after.to_datetime().into(),
+        );
+
+        let alt_names = match certificate.subject_alternative_name() {
+            Ok(Some(extension)) => extension
+                .value
+                .general_names
+                .iter()
+                .filter_map(general_name)
+                .collect(),
+            Ok(None) => Vec::new(),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        let key = certificate.public_key();
+        let key_algorithm = match key.parsed().map(|key| key.key_size()) {
+            Ok(bits) if bits > 0 => {
+                format!("{} ({bits} bits)", algorithm(&key.algorithm.algorithm))
+            }
+            _ => algorithm(&key.algorithm.algorithm),
+        };
+        let serial = certificate.raw_serial();
+
+        Ok(PeerCertificate {
+            subject: certificate.subject().to_string(),
+            issuer: certificate.issuer().to_string(),
+            alt_names,
+            not_before: rfc3339(not_before),
+            not_after: rfc3339(not_after),
+            serial: hex(serial.strip_prefix(&[0]).unwrap_or(serial)),
+            signature_algorithm: algorithm(&certificate.signature_algorithm.algorithm),
+            key_algorithm,
+            fingerprint: hex(&Sha256::digest(der)),
+            validity: (not_before, not_after),
+        })
+    }
+
+    pub fn valid_from(&self) -> SystemTime {
+        self.validity.0
+    }
+
+    pub fn valid_until(&self) -> SystemTime {
+        self.validity.1
+    }
+
+    /// Whether the certificate is for `host`, according to its subject alternative names
+    /// (`*.` wildcards standing for a single label).
+    pub fn matches_host(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let ip = host.parse::<IpAddr>().ok();
+
+        self.alt_names.iter().any(|name| {
+            if let Some(name) = name.strip_prefix("DNS:") {
+                let name = name.to_ascii_lowercase();
+                match name.strip_prefix("*.") {
+                    Some(parent) => host
+                        .split_once('.')
+                        .is_some_and(|(label, rest)| !label.is_empty() && rest == parent),
+                    None => name == host,
+                }
+            } else if let Some(name) = name.strip_prefix("IP:") {
+                ip.is_some_and(|ip| name.parse() == Ok(ip))
+            } else {
+                false
+            }
+        })
+    }
+}
+
+/// A name of the kinds that identify a server.
+fn general_name(name: &GeneralName) -> Option<String> {
+    Some(match name {
+        GeneralName::RFC822Name(email) => format!("email:{email}"),
+        GeneralName::DNSName(name) => format!("DNS:{name}"),
+        GeneralName::URI(uri) => format!("URI:{uri}"),
+        GeneralName::IPAddress(ip) => match ip.len() {
+            4 => format!("IP:{}", Ipv4Addr::from(<[u8; 4]>::try_from(*ip).ok()?)),
+            16 => format!("IP:{}", Ipv6Addr::from(<[u8; 16]>::try_from(*ip).ok()?)),
+            _ => format!("IP:{}", hex(ip)),
+        },
+        _ => return None,
+    })
+}
+
+/// The usual name of an algorithm, or its OID in dotted notation.
+fn algorithm(oid: &Oid) -> String {
+    oid2sn(oid, oid_registry()).map_or_else(|_| oid.to_id_string(), str::to_string)
+}
+
+fn rfc3339(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 3);
+    for (i, b) in bytes.iter().enumerate() {
+        if i > 0 {
+            hex.push(':');
+        }
+        let _ = write!(hex, "{b:02X}");
+    }
+    hex
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+    use super::*;
+
+    /// A self-signed ECDSA certificate for `*.acme.test`.
+    const WILDCARD: &str = concat!(
+        "MIIB0DCCAXegAwIBAgIULhkQHfCwj4OEvRITRnr/o7MQ4JMwCgYIKoZIzj0EAwIwMjELMAkGA1UEBhMCRlIxDTAL",
+        "BgNVBAoMBEFjbWUxFDASBgNVBAMMCyouYWNtZS50ZXN0MB4XDTI2MTAxNzEyMTU1M1oXDTI2MTAyMjEyMTU1M1ow",
+        "MjELMAkGA1UEBhMCRlIxDTALBgNVBAoMBEFjbWUxFDASBgNVBAMMCyouYWNtZS50ZXN0MFkwEwYHKoZIzj0CAQYI",
+        "KoZIzj0DAQcDQgAEuI7BbWFn8vdzC3E4eOf97sN8EGL08ryfhVL0bW7ND9KOyfQwrVFJ7P9E0LDN7R6anDWJIEVw",
+        "BuUWbMftsE18LKNrMGkwHQYDVR0OBBYEFO82QNFy6ExXEOQ2F95eXwqCG/NJMB8GA1UdIwQYMBaAFO82QNFy6ExX",
+        "EOQ2F95eXwqCG/NJMA8GA1UdEwEB/wQFMAMBAf8wFgYDVR0RBA8wDYILKi5hY21lLnRlc3QwCgYIKoZIzj0EAwID",
+        "RwAwRAIgcQ3gUF+NlfS2f1mez01uSkeo9oB6pxzlvWjBHiQmtkwCIHjKGMk6SUpoOCmpnrZI+FdlkCazJ7X9On9f",
+        "NCjcCg+0",
+    );
+
+    fn wildcard() -> PeerCertificate {
+        PeerCertificate::parse(&BASE64.decode(WILDCARD).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn reads_certificate_fields() {
+        let certificate = wildcard();
+        assert_eq!(certificate.subject, "C=FR, O=Acme, CN=*.acme.test");
+        assert_eq!(certificate.issuer, certificate.subject);
+        assert_eq!(certificate.alt_names, ["DNS:*.acme.test"]);
+        assert_eq!(certificate.not_before, "2026-10-17T12:15:53Z");
+        assert_eq!(certificate.not_after, "2026-10-22T12:15:53Z");
+        assert_eq!(
+            certificate.serial,
+            "2E:19:10:1D:F0:B0:8F:83:84:BD:12:13:46:7A:FF:A3:B3:10:E0:93"
+        );
+        assert_eq!(certificate.signature_algorithm, "ecdsa-with-SHA256");
+        assert_eq!(certificate.key_algorithm, "id-ecPublicKey (256 bits)");
+        assert_eq!(
+            certificate.fingerprint,
+            "3D:DF:97:A0:D1:04:B4:82:8C:55:46:52:C5:DB:1F:EA:\
+             60:43:4A:3D:32:BE:D9:64:2F:E6:40:FE:95:1E:CB:5A"
+        );
+        assert!(certificate.valid_from() < certificate.valid_until());
+    }
+
+    #[test]
+    fn matches_wildcard_names() {
+        let certificate = wildcard();
+        assert!(certificate.matches_host("api.acme.test"));
+        assert!(certificate.matches_host("API.Acme.Test."));
+        assert!(!certificate.matches_host("acme.test"));
+        assert!(!certificate.matches_host("a.b.acme.test"));
+        assert!(!certificate.matches_host("127.0.0.1"));
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(PeerCertificate::parse(b"not a certificate").is_err());
+        assert!(PeerCertificate::parse(&BASE64.decode(WILDCARD).unwrap()[..100]).is_err());
+    }
+}
@@ -0,0 +1,815 @@
+use std::{
+    fmt, fs, io,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use futures_util::{
+    stream::{SplitSink, SplitStream},
+    StreamExt,
+};
+use http::{
+    header::{SEC_WEBSOCKET_EXTENSIONS, SEC_WEBSOCKET_PROTOCOL},
+    uri::InvalidUri,
+    HeaderName, HeaderValue, StatusCode, Uri,
+};
+use p12_keystore::KeyStore;
+use rustls_pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio::net::TcpStream;
+#[cfg(unix)]
+use tokio::net::UnixStream;
+use tokio_rustls::{
+    rustls::{
+        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        crypto::{self, ring, CryptoProvider},
+        ClientConfig, DigitallySignedStruct, ProtocolVersion, RootCertStore, SignatureScheme,
+    },
+    TlsConnector,
+};
+use tokio_websockets::{ClientBuilder, Connector, MaybeTlsStream, Message, WebSocketStream};
+
+use crate::{
+    certificate::PeerCertificate,
+    proxy::{self, Proxy},
+    transport::Transport,
+};
+
+pub type WS = WebSocketStream<MaybeTlsStream<Transport>>;
+
+/// An extra HTTP header sent along with the WebSocket handshake request.
+#[derive(Debug, Clone)]
+pub struct Header {
+    name: HeaderName,
+    value: HeaderValue,
+}
+
+impl FromStr for Header {
+    type Err = String;
+
+    /// Parses a header in the `Name: value` format, as in curl's `-H`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((name, value)) = s.split_once(':') else {
+            return Err(format!("expected `Name: value`, got `{s}`"));
+        };
+        let name = HeaderName::from_str(name.trim()).map_err(|e| e.to_string())?;
+        let value = HeaderValue::from_str(value.trim()).map_err(|e| e.to_string())?;
+
+        Ok(Header { name, value })
+    }
+}
+
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.value.to_str().unwrap_or("<non-ASCII value>");
+        write!(f, "{}: {}", self.name, value)
+    }
+}
+
+/// Checks that `protocol` is a valid subprotocol name, i.e. a non-empty HTTP token.
+pub fn parse_protocol(protocol: &str) -> Result<String, String> {
+    let is_token_char = |c: char| c.is_ascii_graphic() && !"\"(),/:;<=>?@[\\]{}".contains(c);
+
+    if protocol.is_empty() || !protocol.chars().all(is_token_char) {
+        return Err(format!("`{protocol}` is not a valid subprotocol name"));
+    }
+
+    Ok(protocol.to_string())
+}
+
+/// Addresses to connect to for a host and port instead of those it resolves to, as in curl's
+/// `--resolve`.
+#[derive(Debug, Clone)]
+pub struct ResolveOverride {
+    host: String,
+    port: u16,
+    addrs: Vec<IpAddr>,
+}
+
+impl FromStr for ResolveOverride {
+    type Err = String;
+
+    /// Parses `host:port:addr`, where `addr` is a comma-separated list of IP addresses, IPv6
+    /// ones possibly in brackets.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let (Some(host), Some(port), Some(addrs)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!("expected `host:port:addr`, got `{s}`"));
+        };
+        if host.is_empty() {
+            return Err("missing host".to_string());
+        }
+        let port = port.parse().map_err(|_| format!("invalid port `{port}`"))?;
+        let addrs = addrs
+            .split(',')
+            .map(|addr| {
+                let addr = addr.trim().trim_start_matches('[').trim_end_matches(']');
+                addr.parse()
+                    .map_err(|_| format!("invalid IP address `{addr}`"))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ResolveOverride {
+            host: host.to_ascii_lowercase(),
+            port,
+            addrs,
+        })
+    }
+}
+
+/// A version of the Internet Protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpFamily {
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    fn of(addr: &SocketAddr) -> Self {
+        match addr {
+            SocketAddr::V4(_) => IpFamily::V4,
+            SocketAddr::V6(_) => IpFamily::V6,
+        }
+    }
+}
+
+impl fmt::Display for IpFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpFamily::V4 => write!(f, "IPv4"),
+            IpFamily::V6 => write!(f, "IPv6"),
+        }
+    }
+}
+
+/// How host names are turned into the addresses to connect to.
+#[derive(Debug, Clone, Default)]
+pub struct ResolveOptions {
+    pub overrides: Vec<ResolveOverride>,
+    /// Only connect to addresses of this family.
+    pub family: Option<IpFamily>,
+}
+
+/// Lifecycle of the connection to the server.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    #[default]
+    Closed,
+    Connecting,
+    Open,
+    Closing,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            ConnectionState::Closed => "CLOSED",
+            ConnectionState::Connecting => "CONNECTING",
+            ConnectionState::Open => "OPEN",
+            ConnectionState::Closing => "CLOSING",
+        };
+        f.write_str(label)
+    }
+}
+
+/// Everything needed to establish a connection.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    pub url: String,
+    pub headers: Vec<Header>,
+    pub protocols: Vec<String>,
+    pub tls: TlsOptions,
+    /// Proxy to connect through. Without it, the one set in the environment is used, if any.
+    pub proxy: Option<Proxy>,
+    pub resolve: ResolveOptions,
+}
+
+/// How `wss://` connections are secured, on top of the system's defaults.
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// Certificates (PEM or DER) to trust in addition to the system's, e.g. a staging CA.
+    pub ca_cert: Option<PathBuf>,
+    /// Certificate to authenticate with: PEM along with `client_key`, or a PKCS#12 bundle
+    /// holding the key otherwise.
+    pub client_cert: Option<PathBuf>,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1) of `client_cert`.
+    pub client_key: Option<PathBuf>,
+    /// Password of the PKCS#12 bundle.
+    pub client_cert_password: String,
+    /// Accept any certificate, for any host name.
+    pub insecure: bool,
+    /// Server name to send, and to expect in the certificate, instead of the URL's host.
+    pub sni: Option<String>,
+}
+
+impl TlsOptions {
+    fn connector(&self) -> Result<Connector, ConnectError> {
+        let provider = Arc::new(ring::default_provider());
+        let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| ConnectError::TlsSetup(e.to_string()))?;
+
+        let builder = if self.insecure {
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(AcceptAnyCertificate(provider)))
+        } else {
+            let mut roots = RootCertStore::empty();
+            // Like browsers, make do with the system's certificates that can be read.
+            roots.add_parsable_certificates(rustls_native_certs::load_native_certs().certs);
+            if let Some(path) = &self.ca_cert {
+                for certificate in read_certificates(path)? {
+                    roots.add(certificate).map_err(|e| {
+                        ConnectError::TlsSetup(format!(
+                            "invalid certificate in {}: {e}",
+                            path.display()
+                        ))
+                    })?;
+                }
+            }
+            builder.with_root_certificates(roots)
+        };
+
+        let mut config = match &self.client_cert {
+            Some(cert_path) => {
+                let (chain, key) = self.client_identity(cert_path)?;
+                builder.with_client_auth_cert(chain, key).map_err(|e| {
+                    ConnectError::TlsSetup(format!(
+                        "invalid client certificate {}: {e}",
+                        cert_path.display()
+                    ))
+                })?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        // WebSocket handshakes are HTTP/1.1 requests.
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(Connector::Rustls(TlsConnector::from(Arc::new(config))))
+    }
+
+    /// The certificate chain and private key to authenticate with.
+    fn client_identity(
+        &self,
+        cert_path: &Path,
+    ) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), ConnectError> {
+        let invalid = |e: &dyn fmt::Display| {
+            ConnectError::TlsSetup(format!(
+                "invalid client certificate {}: {e}",
+                cert_path.display()
+            ))
+        };
+        let cert = read(cert_path)?;
+
+        if let Some(key_path) = &self.client_key {
+            let chain = CertificateDer::pem_slice_iter(&cert)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| invalid(&e))?;
+            let key = PrivateKeyDer::from_pem_slice(&read(key_path)?).map_err(|e| {
+                ConnectError::TlsSetup(format!("invalid key {}: {e}", key_path.display()))
+            })?;
+            return Ok((chain, key));
+        }
+
+        let store =
+            KeyStore::from_pkcs12(&cert, &self.client_cert_password).map_err(|e| invalid(&e))?;
+        let (_, key_chain) = store
+            .private_key_chain()
+            .ok_or_else(|| invalid(&"no private key in the bundle"))?;
+        let chain = key_chain
+            .chain()
+            .iter()
+            .map(|certificate| CertificateDer::from(certificate.as_der().to_vec()))
+            .collect();
+        let key = PrivateKeyDer::try_from(key_chain.key().to_vec()).map_err(|e| invalid(&e))?;
+        Ok((chain, key))
+    }
+}
+
+/// Accepts any certificate, for any host name, for `--insecure`. The handshake signatures are
+/// still checked, as they are part of the protocol rather than of trusting the server.
+#[derive(Debug)]
+struct AcceptAnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, tokio_rustls::rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, tokio_rustls::rustls::Error> {
+        crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.0.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, ConnectError> {
+    fs::read(path)
+        .map_err(|e| ConnectError::TlsSetup(format!("cannot read {}: {e}", path.display())))
+}
+
+/// The certificates in a PEM file, or the one in a DER file.
+fn read_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, ConnectError> {
+    let bytes = read(path)?;
+    if !bytes
+        .windows(b"-----BEGIN".len())
+        .any(|w| w == b"-----BEGIN")
+    {
+        return Ok(vec![CertificateDer::from(bytes)]);
+    }
+
+    let certificates = CertificateDer::pem_slice_iter(&bytes)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            ConnectError::TlsSetup(format!("invalid certificate in {}: {e}", path.display()))
+        })?;
+    if certificates.is_empty() {
+        return Err(ConnectError::TlsSetup(format!(
+            "no certificate in {}",
+            path.display()
+        )));
+    }
+    Ok(certificates)
+}
+
+/// What the server answered to a successful handshake.
+#[derive(Debug, Clone)]
+pub struct Handshake {
+    pub status: StatusCode,
+    pub headers: Vec<(String, String)>,
+    pub protocol: Option<String>,
+    pub extensions: Option<String>,
+    /// The TLS session, for `wss://` connections.
+    pub tls: Option<TlsInfo>,
+    /// The proxy the connection goes through, without its credentials.
+    pub proxy: Option<String>,
+    /// The address connected to, which is the proxy's when there is one. None over a Unix
+    /// socket.
+    pub peer_addr: Option<SocketAddr>,
+}
+
+/// What was negotiated with the server over TLS.
+#[derive(Debug, Clone)]
+pub struct TlsInfo {
+    /// The name sent as SNI, and checked against the certificate.
+    pub server_name: String,
+    /// Whether the certificate was verified, i.e. `--insecure` was not given.
+    pub verified: bool,
+    /// Protocol version, such as `TLS 1.3`.
+    pub version: Option<String>,
+    /// Cipher suite, such as `TLS13_AES_256_GCM_SHA384`.
+    pub cipher_suite: Option<String>,
+    pub alpn: Option<String>,
+    /// The certificates sent by the server, its own first, each one or why it could not be
+    /// read.
+    pub chain: Vec<Result<PeerCertificate, String>>,
+}
+
+impl TlsInfo {
+    fn of<S>(
+        stream: &tokio_rustls::client::TlsStream<S>,
+        server_name: &str,
+        verified: bool,
+    ) -> Self {
+        let (_, connection) = stream.get_ref();
+
+        TlsInfo {
+            server_name: server_name.to_string(),
+            verified,
+            version: connection.protocol_version().map(|version| match version {
+                ProtocolVersion::TLSv1_2 => "TLS 1.2".to_string(),
+                ProtocolVersion::TLSv1_3 => "TLS 1.3".to_string(),
+                version => format!("{version:?}"),
+            }),
+            cipher_suite: connection
+                .negotiated_cipher_suite()
+                .map(|suite| format!("{:?}", suite.suite())),
+            alpn: connection
+                .alpn_protocol()
+                .map(|alpn| String::from_utf8_lossy(alpn).into_owned()),
+            chain: connection
+                .peer_certificates()
+                .unwrap_or_default()
+                .iter()
+                .map(|der| PeerCertificate::parse(der))
+                .collect(),
+        }
+    }
+}
+
+/// The reason why a connection could not be established.
+#[derive(Debug)]
+pub enum ConnectError {
+    InvalidUri(InvalidUri),
+    UnsupportedScheme(Option<String>),
+    MissingHost,
+    MissingSocketPath,
+    DisallowedHeader(HeaderName),
+    InvalidProtocols,
+    Dns {
+        host: String,
+        error: io::Error,
+    },
+    Tcp {
+        addr: SocketAddr,
+        error: io::Error,
+    },
+    #[cfg(unix)]
+    Unix {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// `ws+unix://` URLs are only supported on Unix.
+    #[cfg(not(unix))]
+    UnixSocketsUnsupported,
+    /// A proxy URL in the environment is invalid.
+    InvalidProxy(String),
+    /// The proxy did not open a tunnel to the server.
+    Proxy {
+        proxy: String,
+        error: String,
+    },
+    /// The TLS settings could not be applied, e.g. a certificate file is missing.
+    TlsSetup(String),
+    Tls(tokio_websockets::Error),
+    Handshake(tokio_websockets::Error),
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectError::InvalidUri(e) => write!(f, "Invalid URI: {e}"),
+            ConnectError::UnsupportedScheme(Some(scheme)) => {
+                write!(
+                    f,
+                    "Unsupported scheme `{scheme}`, expected `ws`, `wss` or `ws+unix`"
+                )
+            }
+            ConnectError::UnsupportedScheme(None) => {
+                write!(
+                    f,
+                    "Missing scheme, expected `ws://`, `wss://` or `ws+unix://`"
+                )
+            }
+            ConnectError::MissingHost => write!(f, "Invalid URI: missing host"),
+            ConnectError::MissingSocketPath => write!(f, "Invalid URI: missing socket path"),
+            ConnectError::DisallowedHeader(name) => {
+                write!(
+                    f,
+                    "Header `{name}` is set by the client and cannot be overridden"
+                )
+            }
+            ConnectError::InvalidProtocols => write!(f, "Invalid subprotocol list"),
+            ConnectError::Dns { host, error } => {
+                write!(f, "DNS lookup for `{host}` failed: {error}")
+            }
+            ConnectError::Tcp { addr, error } => {
+                write!(f, "TCP connection to {addr} failed: {error}")
+            }
+            #[cfg(unix)]
+            ConnectError::Unix { path, error } => {
+                write!(f, "Connection to socket {} failed: {error}", path.display())
+            }
+            #[cfg(not(unix))]
+            ConnectError::UnixSocketsUnsupported => {
+                write!(f, "Unix domain sockets are not supported on this platform")
+            }
+            ConnectError::InvalidProxy(e) => write!(f, "Invalid proxy: {e}"),
+            ConnectError::Proxy { proxy, error } => write!(f, "Proxy {proxy} failed: {error}"),
+            ConnectError::TlsSetup(e) => write!(f, "TLS setup failed: {e}"),
+            ConnectError::Tls(e) => write!(f, "TLS handshake failed: {e}"),
+            ConnectError::Handshake(tokio_websockets::Error::Upgrade(
+                tokio_websockets::upgrade::Error::DidNotSwitchProtocols(code),
+            )) => {
+                let reason = StatusCode::from_u16(*code)
+                    .ok()
+                    .and_then(|s| s.canonical_reason())
+                    .unwrap_or("");
+                write!(
+                    f,
+                    "Server answered HTTP {code} {reason} instead of 101 Switching Protocols"
+                )
+            }
+            ConnectError::Handshake(e) => write!(f, "WebSocket handshake failed: {e}"),
+        }
+    }
+}
+
+impl ConnectError {
+    /// Whether trying again might succeed, as opposed to errors in the connection settings.
+    pub fn is_retryable(&self) -> bool {
+        #[cfg(not(unix))]
+        if matches!(self, ConnectError::UnixSocketsUnsupported) {
+            return false;
+        }
+        !matches!(
+            self,
+            ConnectError::InvalidUri(_)
+                | ConnectError::UnsupportedScheme(_)
+                | ConnectError::MissingHost
+                | ConnectError::MissingSocketPath
+                | ConnectError::DisallowedHeader(_)
+                | ConnectError::InvalidProtocols
+                | ConnectError::TlsSetup(_)
+                | ConnectError::InvalidProxy(_)
+        )
+    }
+}
+
+impl std::error::Error for ConnectError {}
+
+pub async fn connect(
+    options: &ConnectOptions,
+) -> Result<(SplitSink<WS, Message>, SplitStream<WS>, Handshake), ConnectError> {
+    // `ws+unix:///run/app.sock:/path` requests `/path` on the server listening on the socket.
+    let (url, socket) = match options.url.split_once("+unix://") {
+        Some((scheme @ ("ws" | "wss"), rest)) => {
+            let (socket, path) = rest.split_once(':').unwrap_or((rest, "/"));
+            if socket.is_empty() {
+                return Err(ConnectError::MissingSocketPath);
+            }
+            let slash = if path.starts_with('/') { "" } else { "/" };
+            (
+                format!("{scheme}://localhost{slash}{path}"),
+                Some(PathBuf::from(socket)),
+            )
+        }
+        _ => (options.url.clone(), None),
+    };
+    let uri = Uri::from_str(&url).map_err(ConnectError::InvalidUri)?;
+
+    let tls = match uri.scheme_str() {
+        Some("wss") => true,
+        Some("ws") => false,
+        scheme => return Err(ConnectError::UnsupportedScheme(scheme.map(String::from))),
+    };
+    // `Uri::host` keeps the square brackets around IPv6 addresses, which do not resolve.
+    let host = uri
+        .host()
+        .ok_or(ConnectError::MissingHost)?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string();
+    let port = uri.port_u16().unwrap_or(if tls { 443 } else { 80 });
+
+    let mut builder = ClientBuilder::from_uri(uri);
+    for header in &options.headers {
+        builder = builder
+            .add_header(header.name.clone(), header.value.clone())
+            .map_err(|_| ConnectError::DisallowedHeader(header.name.clone()))?;
+    }
+    if !options.protocols.is_empty() {
+        let value = HeaderValue::from_str(&options.protocols.join(", "))
+            .map_err(|_| ConnectError::InvalidProtocols)?;
+        builder = builder
+            .add_header(SEC_WEBSOCKET_PROTOCOL, value)
+            .map_err(|_| ConnectError::DisallowedHeader(SEC_WEBSOCKET_PROTOCOL))?;
+    }
+
+    let proxy = match (&options.proxy, &socket) {
+        (_, Some(_)) => None,
+        (Some(proxy), None) => Some(proxy.clone()).filter(|_| !proxy::is_excluded(&host)),
+        (None, None) => Proxy::from_env(tls, &host).map_err(ConnectError::InvalidProxy)?,
+    };
+    let stream = match (socket, &proxy) {
+        #[cfg(unix)]
+        (Some(path), _) => Transport::Unix(
+            UnixStream::connect(&path)
+                .await
+                .map_err(|error| ConnectError::Unix { path, error })?,
+        ),
+        #[cfg(not(unix))]
+        (Some(_), _) => return Err(ConnectError::UnixSocketsUnsupported),
+        (None, Some(proxy)) => Transport::Tcp(proxy.tunnel(&host, port, &options.resolve).await?),
+        (None, None) => Transport::Tcp(connect_tcp(&host, port, &options.resolve).await?),
+    };
+
+    let peer_addr = match &stream {
+        Transport::Tcp(stream) => stream.peer_addr().ok(),
+        #[cfg(unix)]
+        Transport::Unix(_) => None,
+    };
+
+    let server_name = options.tls.sni.as_deref().unwrap_or(&host);
+    let stream = if tls {
+        let connector = options.tls.connector()?;
+        connector
+            .wrap(server_name, stream)
+            .await
+            .map_err(ConnectError::Tls)?
+    } else {
+        MaybeTlsStream::Plain(stream)
+    };
+    let tls_info = match &stream {
+        MaybeTlsStream::Rustls(stream) => {
+            Some(TlsInfo::of(stream, server_name, !options.tls.insecure))
+        }
+        _ => None,
+    };
+
+    let (client, response) = builder
+        .connect_on(stream)
+        .await
+        .map_err(ConnectError::Handshake)?;
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .map(|v: &HeaderValue| String::from_utf8_lossy(v.as_bytes()).into_owned())
+    };
+    let handshake = Handshake {
+        status: response.status(),
+        headers: response
+            .headers()
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect(),
+        protocol: header(SEC_WEBSOCKET_PROTOCOL),
+        extensions: header(SEC_WEBSOCKET_EXTENSIONS),
+        tls: tls_info,
+        proxy: proxy.map(|proxy| proxy.to_string()),
+        peer_addr,
+    };
+
+    let (sink, stream) = client.split();
+    Ok((sink, stream, handshake))
+}
+
+/// Connects to each address of `host` in turn, as curl does, until one accepts the connection.
+/// If none does, the error is that of the last one.
+pub async fn connect_tcp(
+    host: &str,
+    port: u16,
+    options: &ResolveOptions,
+) -> Result<TcpStream, ConnectError> {
+    let mut last_error = None;
+    for addr in resolve(host, port, options).await? {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok(stream),
+            Err(error) => last_error = Some(ConnectError::Tcp { addr, error }),
+        }
+    }
+
+    Err(last_error.expect("`resolve` returns at least one address"))
+}
+
+/// The addresses `host` resolves to, or is given with `--resolve`, of the family asked for if
+/// any. There is always at least one.
+pub async fn resolve(
+    host: &str,
+    port: u16,
+    options: &ResolveOptions,
+) -> Result<Vec<SocketAddr>, ConnectError> {
+    let dns_error = |error| ConnectError::Dns {
+        host: host.to_string(),
+        error,
+    };
+
+    let overridden = options
+        .overrides
+        .iter()
+        .find(|o| o.port == port && o.host.eq_ignore_ascii_case(host));
+    let addrs: Vec<SocketAddr> = match overridden {
+        Some(o) => o
+            .addrs
+            .iter()
+            .map(|&ip| SocketAddr::new(ip, port))
+            .collect(),
+        None => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(dns_error)?
+            .collect(),
+    };
+
+    let addrs: Vec<_> = addrs
+        .into_iter()
+        .filter(|addr| {
+            options
+                .family
+                .is_none_or(|family| IpFamily::of(addr) == family)
+        })
+        .collect();
+    if addrs.is_empty() {
+        let message = match options.family {
+            Some(family) => format!("no {family} addresses found"),
+            None => "no addresses found".to_string(),
+        };
+        return Err(dns_error(io::Error::new(io::ErrorKind::NotFound, message)));
+    }
+
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_resolve_overrides() {
+        let o: ResolveOverride = "Example.com:443:127.0.0.1".parse().unwrap();
+        assert_eq!(o.host, "example.com");
+        assert_eq!(o.port, 443);
+        assert_eq!(o.addrs, [IpAddr::from([127, 0, 0, 1])]);
+
+        let o: ResolveOverride = "example.com:8080:[::1], 10.0.0.2,fe80::1".parse().unwrap();
+        assert_eq!(
+            o.addrs,
+            [
+                "::1".parse::<IpAddr>().unwrap(),
+                "10.0.0.2".parse().unwrap(),
+                "fe80::1".parse().unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_resolve_overrides() {
+        for invalid in [
+            "example.com",
+            "example.com:443",
+            ":443:127.0.0.1",
+            "example.com:https:127.0.0.1",
+            "example.com:70000:127.0.0.1",
+            "example.com:443:",
+            "example.com:443:localhost",
+            "example.com:443:127.0.0.1,",
+        ] {
+            assert!(invalid.parse::<ResolveOverride>().is_err(), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_overridden_hosts_by_family() {
+        let options = |family| ResolveOptions {
+            overrides: vec!["api.test:443:10.0.0.1,::2,10.0.0.3".parse().unwrap()],
+            family,
+        };
+        let resolved = |family, host, port| async move {
+            resolve(host, port, &options(family))
+                .await
+                .map(|addrs| addrs.iter().map(SocketAddr::to_string).collect::<Vec<_>>())
+        };
+
+        assert_eq!(
+            resolved(None, "API.test", 443).await.unwrap(),
+            ["10.0.0.1:443", "[::2]:443", "10.0.0.3:443"]
+        );
+        assert_eq!(
+            resolved(Some(IpFamily::V6), "api.test", 443).await.unwrap(),
+            ["[::2]:443"]
+        );
+
+        let only_v4 = ResolveOptions {
+            overrides: vec!["api.test:443:10.0.0.1".parse().unwrap()],
+            family: Some(IpFamily::V6),
+        };
+        let error = resolve("api.test", 443, &only_v4).await.unwrap_err();
+        assert!(
+            error.to_string().contains("no IPv6 addresses found"),
+            "{error}"
+        );
+        // Other ports are not overridden.
+        assert!(resolved(None, "api.test", 80).await.is_err());
+    }
+}
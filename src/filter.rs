@@ -0,0 +1,174 @@
+use std::{ops::Range, str::FromStr};
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::{
+    json::{self, JsonPath},
+    message::{Author, ChatMessage},
+};
+
+/// What to look for in the message log, when searching or filtering it.
+#[derive(Debug, Clone)]
+pub enum Query {
+    /// Messages whose text matches, either a `/regex/` or a plain substring. Substrings are
+    /// case-insensitive unless they contain uppercase letters.
+    Pattern(Regex),
+    /// Messages by `author:USER`, `author:ORIG` or `author:SYS`.
+    Author(Author),
+    /// JSON messages with (or without, for `!=`) the given value at a path, as in
+    /// `$.type == "tick"`.
+    Json {
+        path: JsonPath,
+        value: Value,
+        equal: bool,
+    },
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Some(author) = s.strip_prefix("author:") {
+            let author = match author.trim().to_ascii_uppercase().as_str() {
+                "USER" => Author::User,
+                "ORIG" => Author::Origin,
+                "SYS" => Author::System,
+                _ => return Err("Author must be USER, ORIG or SYS".to_string()),
+            };
+            return Ok(Query::Author(author));
+        }
+
+        if s.starts_with('$') {
+            let (path, rest) = JsonPath::parse_prefix(s).ok_or("Invalid JSON path")?;
+            let rest = rest.trim_start();
+            let (equal, value) = if let Some(value) = rest.strip_prefix("==") {
+                (true, value)
+            } else if let Some(value) = rest.strip_prefix("!=") {
+                (false, value)
+            } else {
+                return Err("Expected `==` or `!=` after the JSON path".to_string());
+            };
+            let value = json::parse(value).ok_or("Invalid JSON value to compare with")?;
+
+            return Ok(Query::Json { path, value, equal });
+        }
+
+        if let Some(regex) = s
+            .strip_prefix('/')
+            .and_then(|s| s.strip_suffix('/'))
+            .filter(|regex| !regex.is_empty())
+        {
+            return Regex::new(regex)
+                .map(Query::Pattern)
+                .map_err(|_| "Invalid regex".to_string());
+        }
+
+        let case = if s.chars().any(char::is_uppercase) {
+            ""
+        } else {
+            "(?i)"
+        };
+        Regex::new(&format!("{case}{}", regex::escape(s)))
+            .map(Query::Pattern)
+            .map_err(|e| e.to_string())
+    }
+}
+
+impl Query {
+    /// Whether `message`, displayed as `text`, is one being looked for.
+    pub fn matches(&self, message: &ChatMessage, text: &str) -> bool {
+        match self {
+            Query::Pattern(regex) => regex.is_match(text),
+            Query::Author(author) => message.author == *author,
+            Query::Json { path, value, equal } => message.json.as_ref().is_some_and(|json| {
+                path.find(json)
+                    .is_some_and(|found| json::same_value(found, value))
+                    == *equal
+            }),
+        }
+    }
+
+    /// Where the query matches within a line of text, for highlighting.
+    pub fn find_in(&self, line: &str) -> Vec<Range<usize>> {
+        match self {
+            Query::Pattern(regex) => regex
+                .find_iter(line)
+                .map(|m| m.range())
+                .filter(|range| !range.is_empty())
+                .collect(),
+            Query::Author(_) | Query::Json { .. } => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Content;
+
+    fn matches(query: &str, author: Author, text: &str) -> bool {
+        let query: Query = query.parse().unwrap();
+        query.matches(
+            &ChatMessage::new(author, Content::Text(text.to_string())),
+            text,
+        )
+    }
+
+    #[test]
+    fn parses_author_queries() {
+        assert!(matches("author:user", Author::User, "hi"));
+        assert!(matches(" author: ORIG ", Author::Origin, "hi"));
+        assert!(!matches("author:SYS", Author::Origin, "hi"));
+        assert!("author:me".parse::<Query>().is_err());
+    }
+
+    #[test]
+    fn parses_json_comparisons() {
+        let tick = r#"{"type": "tick", "n": 1.0, "data": {"a": [1, 2]}}"#;
+        assert!(matches(r#"$.type == "tick""#, Author::Origin, tick));
+        assert!(matches(r#"$.type=="tick""#, Author::Origin, tick));
+        assert!(!matches(r#"$.type != "tick""#, Author::Origin, tick));
+        assert!(matches("$.n == 1", Author::Origin, tick));
+        assert!(matches(r#"$.data == {"a": [1, 2]}"#, Author::User, tick));
+        assert!(matches("$.data.a[1] == 2", Author::Origin, tick));
+        // A missing value is never equal, so `!=` shows it.
+        assert!(!matches("$.missing == null", Author::Origin, tick));
+        assert!(matches("$.missing != null", Author::Origin, tick));
+        // Only messages holding JSON are compared at all.
+        assert!(!matches(r#"$.type != "tock""#, Author::Origin, "tick"));
+
+        for invalid in [
+            "$.type",
+            "$.type = 1",
+            "$.type == tick",
+            "$[ == 1",
+            "$.type ==",
+        ] {
+            assert!(invalid.parse::<Query>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn parses_patterns() {
+        assert!(matches("/^t.c+k$/", Author::Origin, "tick"));
+        assert!(!matches("/^t.c+k$/", Author::Origin, "a tick"));
+        assert!("/(/".parse::<Query>().is_err());
+
+        // Plain text is literal, and case-insensitive only when all lowercase.
+        assert!(matches("a.b", Author::User, "A.B"));
+        assert!(!matches("a.b", Author::User, "axb"));
+        assert!(!matches("A.b", Author::User, "a.b"));
+        assert!(matches("/", Author::User, "a/b"));
+    }
+
+    #[test]
+    fn finds_matches_to_highlight() {
+        let query: Query = "/o*/".parse().unwrap();
+        assert_eq!(query.find_in("foo bor"), [1..3, 5..6]);
+        let query: Query = "author:SYS".parse().unwrap();
+        assert!(query.find_in("anything").is_empty());
+    }
+}
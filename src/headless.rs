@@ -0,0 +1,192 @@
+use std::{
+    io::{self, Write},
+    process::ExitCode,
+    time::Duration,
+};
+
+use futures_util::{stream::SplitSink, SinkExt, StreamExt};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio_websockets::{CloseCode, Message};
+
+use crate::{
+    connection::{connect, ConnectOptions, WS},
+    message::{Author, ChatMessage, Content},
+    transcript,
+};
+
+/// How received frames are printed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// The payload of data frames as is, text frames followed by a newline.
+    #[default]
+    Raw,
+    /// Every frame as a JSON object per line, as written by `--log`.
+    Jsonl,
+}
+
+/// What to do without the TUI.
+#[derive(Debug, Clone, Default)]
+pub struct HeadlessOptions {
+    /// Messages to send, in the syntax of the message input. Read from stdin if empty.
+    pub messages: Vec<String>,
+    /// Stop once this many data frames are received. Without it, stop when the server closes
+    /// the connection.
+    pub wait_for: Option<usize>,
+    /// Give up after this long, connection included.
+    pub timeout: Option<Duration>,
+    pub format: OutputFormat,
+}
+
+/// How a headless run ended, which is the exit status of the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Everything was sent and all awaited frames were received.
+    Done = 0,
+    /// A message could not be read, parsed or sent.
+    Failed = 1,
+    // 2 is what clap exits with on invalid arguments.
+    ConnectFailed = 3,
+    TimedOut = 4,
+    /// The connection ended before the awaited frames were received.
+    Closed = 5,
+}
+
+impl From<Outcome> for ExitCode {
+    fn from(outcome: Outcome) -> Self {
+        ExitCode::from(outcome as u8)
+    }
+}
+
+/// Connects, sends the messages and prints the frames received to stdout, reporting errors
+/// on stderr.
+pub async fn run(connect_options: ConnectOptions, options: HeadlessOptions) -> Outcome {
+    let Some(timeout) = options.timeout else {
+        return exchange(&connect_options, &options).await;
+    };
+
+    match tokio::time::timeout(timeout, exchange(&connect_options, &options)).await {
+        Ok(outcome) => outcome,
+        Err(_) => {
+            eprintln!("Timed out after {:.1}s", timeout.as_secs_f64());
+            Outcome::TimedOut
+        }
+    }
+}
+
+async fn exchange(connect_options: &ConnectOptions, options: &HeadlessOptions) -> Outcome {
+    let (mut sink, mut stream, _) = match connect(connect_options).await {
+        Ok(connection) => connection,
+        Err(e) => {
+            eprintln!("Connection failed: {e}");
+            return Outcome::ConnectFailed;
+        }
+    };
+
+    for message in &options.messages {
+        if let Err(outcome) = send(&mut sink, message).await {
+            return outcome;
+        }
+    }
+    let mut lines = options
+        .messages
+        .is_empty()
+        .then(|| BufReader::new(tokio::io::stdin()).lines());
+
+    let mut received = 0;
+    loop {
+        // Everything is sent and nothing more is awaited.
+        if lines.is_none() && options.wait_for.is_some_and(|n| received >= n) {
+            close(&mut sink).await;
+            return Outcome::Done;
+        }
+
+        let frame = tokio::select! {
+            line = async { lines.as_mut()?.next_line().await.transpose() }, if lines.is_some() => {
+                match line {
+                    Some(Ok(line)) => {
+                        if let Err(outcome) = send(&mut sink, &line).await {
+                            return outcome;
+                        }
+                    }
+                    Some(Err(e)) => {
+                        eprintln!("Error reading stdin: {e}");
+                        return Outcome::Failed;
+                    }
+                    None => lines = None,
+                }
+                continue;
+            }
+            frame = stream.next() => frame,
+        };
+
+        let content = match frame {
+            Some(Ok(message)) => match Content::from_message(&message) {
+                Some(content) => content,
+                None => continue,
+            },
+            Some(Err(e)) => {
+                eprintln!("Connection error: {e}");
+                return Outcome::Closed;
+            }
+            None => {
+                eprintln!("Connection ended without a close frame");
+                return Outcome::Closed;
+            }
+        };
+
+        if let Err(e) = print(&content, &connect_options.url, options.format) {
+            eprintln!("Error writing to stdout: {e}");
+            return Outcome::Failed;
+        }
+
+        match content {
+            Content::Text(_) | Content::Binary(_) => received += 1,
+            // Frames beyond those awaited may arrive while stdin is still being read.
+            Content::Close { .. } if options.wait_for.is_none_or(|n| received >= n) => {
+                return Outcome::Done
+            }
+            Content::Close { .. } => {
+                eprintln!(
+                    "Connection closed by the server after {received} of {} frames",
+                    options.wait_for.unwrap_or_default()
+                );
+                return Outcome::Closed;
+            }
+            Content::Ping(_) | Content::Pong { .. } => {}
+        }
+    }
+}
+
+async fn send(sink: &mut SplitSink<WS, Message>, input: &str) -> Result<(), Outcome> {
+    let content = Content::parse_input(input).map_err(|e| {
+        eprintln!("Invalid message `{input}`: {e}");
+        Outcome::Failed
+    })?;
+
+    sink.send(content.to_message()).await.map_err(|e| {
+        eprintln!("Error sending message: {e}");
+        Outcome::Failed
+    })
+}
+
+async fn close(sink: &mut SplitSink<WS, Message>) {
+    let _ = sink
+        .send(Message::close(Some(CloseCode::NORMAL_CLOSURE), ""))
+        .await;
+}
+
+fn print(content: &Content, url: &str, format: OutputFormat) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    match (format, content) {
+        (OutputFormat::Raw, Content::Text(text)) => writeln!(stdout, "{text}")?,
+        (OutputFormat::Raw, Content::Binary(data)) => stdout.write_all(data)?,
+        (OutputFormat::Raw, _) => {}
+        (OutputFormat::Jsonl, content) => {
+            let message = ChatMessage::new(Author::Origin, content.clone());
+            writeln!(stdout, "{}", transcript::record(&message, url))?;
+        }
+    }
+
+    stdout.flush()
+}
@@ -0,0 +1,131 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+/// How many entries are kept per URL.
+const MAX_ENTRIES: usize = 1000;
+
+/// Messages sent to a URL, oldest first, persisted across runs in a file per URL.
+#[derive(Debug, Default)]
+pub struct History {
+    /// `None` if there is nowhere to store the history, in which case it is only kept in memory.
+    path: Option<PathBuf>,
+    entries: Vec<String>,
+}
+
+impl History {
+    /// Loads the history of `url`. A missing or unreadable file just means an empty history.
+    pub fn load(url: &str) -> Self {
+        let path = history_dir().map(|dir| dir.join(file_name(url)));
+        let mut entries: Vec<_> = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|file| file.lines().map(unescape).collect())
+            .unwrap_or_default();
+
+        if entries.len() > MAX_ENTRIES {
+            entries.drain(..entries.len() - MAX_ENTRIES);
+            if let Some(path) = &path {
+                let file: String = entries.iter().map(|e| escape(e) + "\n").collect();
+                let _ = open(path, false).and_then(|mut f| f.write_all(file.as_bytes()));
+            }
+        }
+
+        History { path, entries }
+    }
+
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+
+    /// Records a sent message, unless it repeats the previous one. Failing to save it is not
+    /// worth interrupting the user for, so it is then only kept in memory.
+    pub fn push(&mut self, entry: &str) {
+        if entry.is_empty() || self.entries.last().is_some_and(|last| last == entry) {
+            return;
+        }
+        self.entries.push(entry.to_string());
+
+        if let Some(path) = &self.path {
+            let _ = append(path, entry);
+        }
+    }
+
+    /// The newest entry containing `query` that is older than `before`, if given.
+    pub fn search(&self, query: &str, before: Option<usize>) -> Option<usize> {
+        let end = before.unwrap_or(self.entries.len()).min(self.entries.len());
+        self.entries[..end].iter().rposition(|e| e.contains(query))
+    }
+}
+
+fn append(path: &Path, entry: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    writeln!(open(path, true)?, "{}", escape(entry))
+}
+
+/// Opens a history file for appending or rewriting it. Sent messages often hold credentials,
+/// so the file is only readable by its owner.
+fn open(path: &Path, append: bool) -> io::Result<File> {
+    let mut options = fs::OpenOptions::new();
+    options
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options.open(path)
+}
+
+/// `$XDG_STATE_HOME/rsocktui/history`, defaulting to `~/.local/state/rsocktui/history`.
+fn history_dir() -> Option<PathBuf> {
+    let state = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))?;
+
+    Some(state.join("rsocktui").join("history"))
+}
+
+/// A file name unique to `url`, which neither reveals it nor grows with it.
+fn file_name(url: &str) -> String {
+    Sha256::digest(url.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Entries are stored one per line, so newlines (and the backslashes escaping them) are
+/// escaped.
+fn escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                entry.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                entry.push('\\');
+                chars.next();
+            }
+            (c, _) => entry.push(c),
+        }
+    }
+
+    entry
+}
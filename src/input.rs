@@ -0,0 +1,306 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// A text input with a cursor and the usual shell editing keys.
+#[derive(Debug, Default, Clone)]
+pub struct LineEditor {
+    text: String,
+    /// Byte offset of the cursor in `text`, always on a char boundary.
+    cursor: usize,
+}
+
+impl LineEditor {
+    /// An editor holding `text`, with the cursor at its end.
+    pub fn new(text: String) -> Self {
+        LineEditor {
+            cursor: text.len(),
+            text,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Replaces the text, moving the cursor to its end.
+    pub fn set(&mut self, text: String) {
+        *self = LineEditor::new(text);
+    }
+
+    pub fn take(&mut self) -> String {
+        std::mem::take(self).text
+    }
+
+    pub fn clear(&mut self) {
+        self.set(String::new());
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.text.insert_str(self.cursor, s);
+        self.cursor += s.len();
+    }
+
+    /// Handles an editing or cursor movement key, returning whether it was one.
+    pub fn on_key(&mut self, key: KeyEvent) -> bool {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Char(c) if !ctrl && !key.modifiers.contains(KeyModifiers::ALT) => {
+                self.text.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            }
+            KeyCode::Char('a' | 'A') if ctrl => self.cursor = self.line_start(),
+            KeyCode::Char('e' | 'E') if ctrl => self.cursor = self.line_end(),
+            KeyCode::Char('w' | 'W') if ctrl => {
+                let start = self.word_start();
+                self.text.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            KeyCode::Char('u' | 'U') if ctrl => {
+                let start = self.line_start();
+                self.text.drain(start..self.cursor);
+                self.cursor = start;
+            }
+            KeyCode::Char('k' | 'K') if ctrl => {
+                self.text.drain(self.cursor..self.line_end());
+            }
+            KeyCode::Backspace => {
+                if let Some(previous) = self.previous_boundary() {
+                    self.text.drain(previous..self.cursor);
+                    self.cursor = previous;
+                }
+            }
+            KeyCode::Delete => {
+                if let Some(next) = self.next_boundary() {
+                    self.text.drain(self.cursor..next);
+                }
+            }
+            KeyCode::Left if ctrl => self.cursor = self.word_start(),
+            KeyCode::Right if ctrl => self.cursor = self.word_end(),
+            KeyCode::Left => self.cursor = self.previous_boundary().unwrap_or(self.cursor),
+            KeyCode::Right => self.cursor = self.next_boundary().unwrap_or(self.cursor),
+            KeyCode::Home => self.cursor = self.line_start(),
+            KeyCode::End => self.cursor = self.line_end(),
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Moves the cursor to the line above or below, keeping its display column where possible.
+    /// Returns false if there is no such line.
+    pub fn move_line(&mut self, up: bool) -> bool {
+        let start = self.line_start();
+        let (_, column) = self.cursor_position();
+
+        let (line_start, line_end) = if up {
+            if start == 0 {
+                return false;
+            }
+            let end = start - 1;
+            (self.text[..end].rfind('\n').map_or(0, |i| i + 1), end)
+        } else {
+            let end = self.line_end();
+            if end == self.text.len() {
+                return false;
+            }
+            let start = end + 1;
+            let next_end = self.text[start..]
+                .find('\n')
+                .map_or(self.text.len(), |i| start + i);
+            (start, next_end)
+        };
+
+        // The first char reaching past the column, so that the cursor does not end up further
+        // right than it was when wide chars are in the way.
+        let mut width = 0;
+        self.cursor = self.text[line_start..line_end]
+            .char_indices()
+            .find(|&(_, c)| {
+                width += c.width().unwrap_or(0);
+                width > column
+            })
+            .map_or(line_end, |(i, _)| line_start + i);
+        true
+    }
+
+    /// The line and display column of the cursor.
+    pub fn cursor_position(&self) -> (usize, usize) {
+        let before = &self.text[..self.cursor];
+        let line = before.matches('\n').count();
+        let column = before[self.line_start()..].width();
+
+        (line, column)
+    }
+
+    fn previous_boundary(&self) -> Option<usize> {
+        self.text[..self.cursor]
+            .char_indices()
+            .next_back()
+            .map(|(i, _)| i)
+    }
+
+    fn next_boundary(&self) -> Option<usize> {
+        self.text[self.cursor..]
+            .chars()
+            .next()
+            .map(|c| self.cursor + c.len_utf8())
+    }
+
+    fn line_start(&self) -> usize {
+        self.text[..self.cursor].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self) -> usize {
+        self.text[self.cursor..]
+            .find('\n')
+            .map_or(self.text.len(), |i| self.cursor + i)
+    }
+
+    /// Start of the word before the cursor, skipping whitespace in between.
+    fn word_start(&self) -> usize {
+        let before = self.text[..self.cursor].trim_end();
+        before.rfind(char::is_whitespace).map_or(0, |i| {
+            i + before[i..].chars().next().map_or(1, char::len_utf8)
+        })
+    }
+
+    /// End of the word after the cursor, skipping whitespace in between.
+    fn word_end(&self) -> usize {
+        let after = &self.text[self.cursor..];
+        let skipped = after.len() - after.trim_start().len();
+        let word = after[skipped..]
+            .find(char::is_whitespace)
+            .unwrap_or(after.len() - skipped);
+
+        self.cursor + skipped + word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text with a `|` where the cursor is.
+    fn shown(editor: &LineEditor) -> String {
+        let mut text = editor.text.clone();
+        text.insert(editor.cursor, '|');
+        text
+    }
+
+    fn press(editor: &mut LineEditor, code: KeyCode, modifiers: KeyModifiers) {
+        assert!(editor.on_key(KeyEvent::new(code, modifiers)));
+    }
+
+    fn ctrl(editor: &mut LineEditor, c: char) {
+        press(editor, KeyCode::Char(c), KeyModifiers::CONTROL);
+    }
+
+    /// An editor holding `text`, with the cursor where its `|` is.
+    fn editor(text: &str) -> LineEditor {
+        let cursor = text.find('|').unwrap();
+        LineEditor {
+            text: text.replacen('|', "", 1),
+            cursor,
+        }
+    }
+
+    #[test]
+    fn edits_multibyte_text() {
+        let mut editor = LineEditor::default();
+        for c in "héllo 日本".chars() {
+            press(&mut editor, KeyCode::Char(c), KeyModifiers::NONE);
+        }
+        assert_eq!(shown(&editor), "héllo 日本|");
+
+        press(&mut editor, KeyCode::Backspace, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Left, KeyModifiers::NONE);
+        assert_eq!(shown(&editor), "héllo |日");
+        press(&mut editor, KeyCode::Home, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Right, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Delete, KeyModifiers::NONE);
+        assert_eq!(shown(&editor), "h|llo 日");
+        editor.insert_str("é");
+        assert_eq!(shown(&editor), "hé|llo 日");
+
+        assert!(!editor.on_key(KeyEvent::new(KeyCode::Char('x'), KeyModifiers::ALT)));
+        assert!(!editor.on_key(KeyEvent::new(KeyCode::F(1), KeyModifiers::NONE)));
+        assert_eq!(shown(&editor), "hé|llo 日");
+    }
+
+    #[test]
+    fn moves_and_deletes_by_word() {
+        let mut editor = editor("ça  va  très| bien");
+        press(&mut editor, KeyCode::Left, KeyModifiers::CONTROL);
+        assert_eq!(shown(&editor), "ça  va  |très bien");
+        press(&mut editor, KeyCode::Left, KeyModifiers::CONTROL);
+        assert_eq!(shown(&editor), "ça  |va  très bien");
+        press(&mut editor, KeyCode::Right, KeyModifiers::CONTROL);
+        press(&mut editor, KeyCode::Right, KeyModifiers::CONTROL);
+        assert_eq!(shown(&editor), "ça  va  très| bien");
+
+        ctrl(&mut editor, 'w');
+        assert_eq!(shown(&editor), "ça  va  | bien");
+        ctrl(&mut editor, 'w');
+        assert_eq!(shown(&editor), "ça  | bien");
+        ctrl(&mut editor, 'w');
+        ctrl(&mut editor, 'w');
+        assert_eq!(shown(&editor), "| bien");
+    }
+
+    #[test]
+    fn edits_the_current_line_only() {
+        let mut editor = editor("first\nsec|ond\nthird");
+        ctrl(&mut editor, 'k');
+        assert_eq!(shown(&editor), "first\nsec|\nthird");
+        ctrl(&mut editor, 'a');
+        assert_eq!(shown(&editor), "first\n|sec\nthird");
+        ctrl(&mut editor, 'e');
+        assert_eq!(shown(&editor), "first\nsec|\nthird");
+        ctrl(&mut editor, 'u');
+        assert_eq!(shown(&editor), "first\n|\nthird");
+
+        // At the start of a line, there is nothing before the cursor on it to delete.
+        ctrl(&mut editor, 'u');
+        assert_eq!(shown(&editor), "first\n|\nthird");
+        // Words are deleted across lines, as in shells.
+        ctrl(&mut editor, 'w');
+        assert_eq!(shown(&editor), "|\nthird");
+    }
+
+    #[test]
+    fn moves_between_lines() {
+        let mut editor = editor("日本語\nab|cdef\nx");
+        assert_eq!(editor.cursor_position(), (1, 2));
+
+        assert!(editor.move_line(true));
+        assert_eq!(shown(&editor), "日|本語\nabcdef\nx");
+        assert_eq!(editor.cursor_position(), (0, 2));
+        assert!(!editor.move_line(true));
+
+        assert!(editor.move_line(false));
+        assert_eq!(shown(&editor), "日本語\nab|cdef\nx");
+        press(&mut editor, KeyCode::End, KeyModifiers::NONE);
+        assert!(editor.move_line(true));
+        assert_eq!(shown(&editor), "日本語|\nabcdef\nx");
+        assert!(editor.move_line(false));
+        assert!(editor.move_line(false));
+        assert_eq!(shown(&editor), "日本語\nabcdef\nx|");
+        assert!(!editor.move_line(false));
+    }
+
+    #[test]
+    fn measures_wide_characters() {
+        let editor = editor("ab\n日本|x");
+        assert_eq!(editor.cursor_position(), (1, 4));
+
+        // Odd columns fall within a wide char, whose start is the closest.
+        let mut editor = LineEditor::new("日本語\nabc".to_string());
+        assert!(editor.move_line(true));
+        assert_eq!(shown(&editor), "日|本語\nabc");
+    }
+}
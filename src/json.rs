@@ -0,0 +1,298 @@
+use serde_json::Value;
+
+/// What a piece of printed JSON is, for syntax highlighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token {
+    Key,
+    String,
+    Number,
+    Bool,
+    Null,
+    Punctuation,
+}
+
+/// A line of printed JSON, as `(token, text)` pieces.
+pub type JsonLine = Vec<(Token, String)>;
+
+/// Parses `s` if it holds a JSON object or array (and nothing else besides whitespace).
+/// Bare scalars are not considered JSON documents, since any number or `true` typed as a
+/// message would be.
+pub fn parse_document(s: &str) -> Option<Value> {
+    let s = s.trim();
+    if !s.starts_with(['{', '[']) {
+        return None;
+    }
+
+    parse(s)
+}
+
+/// Parses any JSON value, scalars included. Numbers keep their digits as written (exponents
+/// only gain an explicit sign) and object members their order, so that printing the value back
+/// barely alters it. If a key is repeated, the last value wins. Nesting is limited to 128
+/// levels, so that hostile payloads cannot overflow the stack.
+pub fn parse(s: &str) -> Option<Value> {
+    serde_json::from_str(s).ok()
+}
+
+/// Prints `json` either on a single line or indented over several ones.
+pub fn lines(json: &Value, expanded: bool) -> Vec<JsonLine> {
+    let mut printer = Printer {
+        lines: vec![Vec::new()],
+        expanded,
+    };
+    printer.value(json, 0);
+
+    printer.lines
+}
+
+/// Whether both values are the same, regardless of how they are written (e.g. `1` and `1.0`,
+/// or `"é"` and `"\u00e9"`) and of the order of object members.
+pub fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(key, value)| b.get(key).is_some_and(|v| same_value(value, v)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+/// A path into a JSON document, such as `$.data.items[0]["last name"]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath(Vec<Segment>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl JsonPath {
+    /// The value at this path in `json`, if there is one.
+    pub fn find<'a>(&self, json: &'a Value) -> Option<&'a Value> {
+        self.0.iter().try_fold(json, |json, segment| match segment {
+            Segment::Key(key) => json.get(key),
+            Segment::Index(i) => json.as_array()?.get(*i),
+        })
+    }
+
+    /// Parses a path at the start of `s`, returning it along with the rest of `s`.
+    pub fn parse_prefix(s: &str) -> Option<(JsonPath, &str)> {
+        let mut rest = s.strip_prefix('$')?;
+        let mut segments = Vec::new();
+
+        loop {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after
+                    .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+                    .unwrap_or(after.len());
+                if end == 0 {
+                    return None;
+                }
+                segments.push(Segment::Key(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']')?;
+                let inside = after[..end].trim();
+                let segment = match inside.parse() {
+                    Ok(i) => Segment::Index(i),
+                    Err(_) if inside.starts_with('"') => {
+                        Segment::Key(serde_json::from_str(inside).ok()?)
+                    }
+                    Err(_) => return None,
+                };
+                segments.push(segment);
+                rest = &after[end + 1..];
+            } else {
+                return Some((JsonPath(segments), rest));
+            }
+        }
+    }
+}
+
+struct Printer {
+    lines: Vec<JsonLine>,
+    expanded: bool,
+}
+
+impl Printer {
+    fn push(&mut self, token: Token, text: impl Into<String>) {
+        self.lines
+            .last_mut()
+            .expect("there is always a line")
+            .push((token, text.into()));
+    }
+
+    /// Starts a new line indented to `depth` when expanded, or separates items by a space
+    /// otherwise.
+    fn break_line(&mut self, depth: usize, compact: &str) {
+        if self.expanded {
+            self.lines
+                .push(vec![(Token::Punctuation, "  ".repeat(depth))]);
+        } else if !compact.is_empty() {
+            self.push(Token::Punctuation, compact);
+        }
+    }
+
+    fn value(&mut self, json: &Value, depth: usize) {
+        match json {
+            Value::Null => self.push(Token::Null, "null"),
+            Value::Bool(b) => self.push(Token::Bool, b.to_string()),
+            Value::Number(n) => self.push(Token::Number, n.to_string()),
+            Value::String(s) => self.push(Token::String, quote(s)),
+            Value::Array(elements) if elements.is_empty() => self.push(Token::Punctuation, "[]"),
+            Value::Object(members) if members.is_empty() => self.push(Token::Punctuation, "{}"),
+            Value::Array(elements) => {
+                self.push(Token::Punctuation, "[");
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        self.push(Token::Punctuation, ",");
+                    }
+                    self.break_line(depth + 1, if i > 0 { " " } else { "" });
+                    self.value(element, depth + 1);
+                }
+                self.break_line(depth, "");
+                self.push(Token::Punctuation, "]");
+            }
+            Value::Object(members) => {
+                self.push(Token::Punctuation, "{");
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        self.push(Token::Punctuation, ",");
+                    }
+                    self.break_line(depth + 1, if i > 0 { " " } else { "" });
+                    self.push(Token::Key, quote(key));
+                    self.push(Token::Punctuation, ": ");
+                    self.value(value, depth + 1);
+                }
+                self.break_line(depth, "");
+                self.push(Token::Punctuation, "}");
+            }
+        }
+    }
+}
+
+/// `s` as a JSON string, quotes included.
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).expect("strings are always valid JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn printed(json: &Value, expanded: bool) -> Vec<String> {
+        lines(json, expanded)
+            .into_iter()
+            .map(|line| line.into_iter().map(|(_, text)| text).collect())
+            .collect()
+    }
+
+    #[test]
+    fn parses_documents() {
+        let json = parse_document(r#" {"b": [1, true, null], "a": "x"} "#).unwrap();
+        assert_eq!(json, json!({"b": [1, true, null], "a": "x"}));
+        let keys: Vec<_> = json.as_object().unwrap().keys().collect();
+        assert_eq!(keys, ["b", "a"]);
+
+        assert_eq!(parse_document("42"), None);
+        assert_eq!(parse("42"), Some(json!(42)));
+        assert_eq!(parse(r#"{"a": 1} x"#), None);
+        assert_eq!(parse(r#"{"a": 1,}"#), None);
+        assert_eq!(parse("[01]"), None);
+    }
+
+    #[test]
+    fn keeps_numbers_as_written() {
+        let json = parse("[1.0, 2.50e-3, 1e3, -0, 123456789012345678901234567890]").unwrap();
+        assert_eq!(
+            printed(&json, false),
+            ["[1.0, 2.50e-3, 1e+3, -0, 123456789012345678901234567890]"]
+        );
+    }
+
+    #[test]
+    fn decodes_escapes_and_surrogate_pairs() {
+        let json = parse(r#""caf\u00e9 \ud83d\ude00 \"quoted\"\n""#).unwrap();
+        assert_eq!(json.as_str(), Some("café 😀 \"quoted\"\n"));
+        assert_eq!(parse(r#""\ud83d""#), None);
+        assert_eq!(parse(r#""\x""#), None);
+    }
+
+    #[test]
+    fn prints_compact_and_expanded() {
+        let json = parse(r#"{"a":[1,2],"b":{},"c":"\t\u0001é"}"#).unwrap();
+        assert_eq!(
+            printed(&json, false),
+            [r#"{"a": [1, 2], "b": {}, "c": "\t\u0001é"}"#]
+        );
+        assert_eq!(
+            printed(&json, true),
+            [
+                "{",
+                r#"  "a": ["#,
+                "    1,",
+                "    2",
+                "  ],",
+                r#"  "b": {},"#,
+                r#"  "c": "\t\u0001é""#,
+                "}",
+            ]
+        );
+
+        let tokens: Vec<_> = lines(&json, false)[0]
+            .iter()
+            .map(|(token, _)| *token)
+            .collect();
+        assert_eq!(tokens[1], Token::Key);
+        assert!(tokens.contains(&Token::Number));
+        assert!(tokens.contains(&Token::String));
+    }
+
+    #[test]
+    fn compares_values() {
+        let same = |a: &str, b: &str| same_value(&parse(a).unwrap(), &parse(b).unwrap());
+        assert!(same(
+            r#"{"a": 1, "b": "é"}"#,
+            r#"{"b": "\u00e9", "a": 1.0}"#
+        ));
+        assert!(same("[1, [2]]", "[1e0, [2.00]]"));
+        assert!(!same("[1, 2]", "[2, 1]"));
+        assert!(!same(r#"{"a": 1}"#, r#"{"a": 1, "b": 2}"#));
+        assert!(!same("1", r#""1""#));
+        assert!(!same("null", "false"));
+    }
+
+    #[test]
+    fn finds_values_by_path() {
+        let json = parse(r#"{"data": {"items": [{"last name": "Doe"}]}, "a": 1, "a": 2}"#).unwrap();
+        let find = |path: &str| {
+            let (path, rest) = JsonPath::parse_prefix(path).unwrap();
+            assert_eq!(rest, "");
+            path.find(&json).cloned()
+        };
+        assert_eq!(find(r#"$.data.items[0]["last name"]"#), Some(json!("Doe")));
+        assert_eq!(find("$.a"), Some(json!(2)));
+        assert_eq!(find("$.data.items[1]"), None);
+        assert_eq!(find("$.data[0]"), None);
+
+        let (_, rest) = JsonPath::parse_prefix("$.a == 1").unwrap();
+        assert_eq!(rest, " == 1");
+        assert_eq!(JsonPath::parse_prefix("$."), None);
+        assert_eq!(JsonPath::parse_prefix("a"), None);
+    }
+
+    #[test]
+    fn limits_nesting() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(parse(&nested(100)).is_some());
+        assert!(parse(&nested(10_000)).is_none());
+    }
+}
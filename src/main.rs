@@ -1,25 +1,336 @@
 pub mod app;
+pub mod certificate;
+pub mod connection;
+pub mod filter;
+pub mod headless;
+pub mod history;
+pub mod input;
+pub mod json;
+pub mod message;
+pub mod proxy;
+pub mod replay;
+pub mod session;
+pub mod transcript;
+pub mod transport;
 
-use app::App;
-use clap::Parser;
+use std::{panic, path::PathBuf, process::ExitCode, time::Duration};
+
+use app::{disable_terminal_features, enable_terminal_features, App};
+use clap::{Parser, Subcommand};
+use color_eyre::eyre::{eyre, WrapErr};
+use connection::{
+    parse_protocol, ConnectOptions, Header, IpFamily, ResolveOptions, ResolveOverride, TlsOptions,
+};
+use headless::{HeadlessOptions, OutputFormat};
+use message::TimestampFormat;
+use proxy::Proxy;
+use replay::{Recording, ReplayTiming};
+use session::{ReconnectPolicy, SessionOptions};
+use transcript::Transcript;
 
 #[derive(Parser, Debug)]
-#[command(version, about, long_about=None)]
+#[command(version, about, long_about=None, args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// URL to connect to: `ws://`, `wss://`, or `ws+unix:///path/to.sock:/path` for a server
+    /// listening on a Unix domain socket.
     #[arg(short, long)]
     url: Option<String>,
+
+    #[command(flatten)]
+    connect: ConnectArgs,
+
+    /// Reconnect automatically when the connection drops.
+    #[arg(long)]
+    reconnect: bool,
+
+    /// Give up reconnecting after this many consecutive failed attempts.
+    #[arg(long, value_name = "N", requires = "reconnect")]
+    max_reconnect_attempts: Option<u32>,
+
+    /// Delay before the first reconnection attempt, doubled on every further attempt.
+    #[arg(long, value_name = "DURATION", default_value = "500ms", value_parser = parse_duration)]
+    reconnect_delay: Duration,
+
+    /// Upper bound for the delay between reconnection attempts.
+    #[arg(long, value_name = "DURATION", default_value = "30s", value_parser = parse_duration)]
+    max_reconnect_delay: Duration,
+
+    /// Message to send every time the connection opens (e.g. to re-subscribe). Can be repeated.
+    #[arg(long = "on-open", value_name = "MESSAGE")]
+    on_open: Vec<String>,
+
+    /// Send a keepalive ping this often.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    ping_interval: Option<Duration>,
+
+    /// Consider the connection dead (and reconnect, with `--reconnect`) when nothing is
+    /// received for this long after a keepalive ping.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "ping_interval")]
+    pong_timeout: Option<Duration>,
+
+    /// How to show message timestamps.
+    #[arg(long, value_enum, default_value_t = TimestampFormat::Absolute)]
+    timestamps: TimestampFormat,
+
+    /// Append every message to this file as JSON Lines, with its direction, timestamp, frame
+    /// type, payload and connection URL.
+    #[arg(long, value_name = "PATH")]
+    log: Option<PathBuf>,
+
+    /// Transcript (as written with `--log`) whose sent messages are replayed with `F9`, to
+    /// compare the responses with the recorded ones.
+    #[arg(long, value_name = "PATH")]
+    replay: Option<PathBuf>,
+
+    /// How fast to replay the transcript.
+    #[arg(long, value_enum, default_value_t = ReplayTiming::Original, requires = "replay")]
+    replay_timing: ReplayTiming,
+}
+
+/// Options of the handshake, shared by the TUI and the headless mode.
+#[derive(clap::Args, Debug)]
+struct ConnectArgs {
+    /// Extra header to send in the handshake, as `Name: value`. Can be repeated.
+    #[arg(short = 'H', long = "header", value_name = "NAME: VALUE")]
+    headers: Vec<Header>,
+
+    /// Subprotocol to offer in the handshake (e.g. `graphql-transport-ws`). Can be repeated.
+    #[arg(short, long = "protocol", value_name = "PROTOCOL", value_parser = parse_protocol)]
+    protocols: Vec<String>,
+
+    /// Trust the certificates in this file (PEM or DER) in addition to the system's, e.g. the
+    /// CA of a staging server.
+    #[arg(long, value_name = "PATH")]
+    ca_cert: Option<PathBuf>,
+
+    /// Authenticate with this certificate: PEM along with `--client-key`, or a PKCS#12 bundle
+    /// holding the key otherwise.
+    #[arg(long, value_name = "PATH")]
+    client_cert: Option<PathBuf>,
+
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1) of `--client-cert`.
+    #[arg(long, value_name = "PATH", requires = "client_cert")]
+    client_key: Option<PathBuf>,
+
+    /// Password of the PKCS#12 bundle given with `--client-cert`.
+    #[arg(
+        long,
+        value_name = "PASSWORD",
+        requires = "client_cert",
+        conflicts_with = "client_key"
+    )]
+    client_cert_password: Option<String>,
+
+    /// Do not verify the server's certificate nor its host name. Dangerous.
+    #[arg(short = 'k', long)]
+    insecure: bool,
+
+    /// Server name to send in the TLS handshake, and to expect in the certificate, instead of
+    /// the URL's host.
+    #[arg(long, value_name = "NAME")]
+    sni: Option<String>,
+
+    /// Connect through this proxy: `http://[user:password@]host:port` for HTTP CONNECT, or
+    /// `socks5://` (`socks5h://` to resolve names on the proxy). Defaults to `HTTPS_PROXY`
+    /// for `wss://`, `HTTP_PROXY` for `ws://`, or `ALL_PROXY`; hosts in `NO_PROXY` are
+    /// reached directly.
+    #[arg(long, value_name = "URL")]
+    proxy: Option<Proxy>,
+
+    /// Connect to ADDR for HOST:PORT instead of resolving HOST, still sending HOST in the Host
+    /// header and as SNI, as curl does. Several comma-separated addresses are tried in turn.
+    /// Can be repeated.
+    #[arg(long = "resolve", value_name = "HOST:PORT:ADDR")]
+    resolve: Vec<ResolveOverride>,
+
+    /// Only connect to IPv4 addresses.
+    #[arg(short = '4', long, conflicts_with = "ipv6")]
+    ipv4: bool,
+
+    /// Only connect to IPv6 addresses.
+    #[arg(short = '6', long)]
+    ipv6: bool,
+}
+
+impl ConnectArgs {
+    fn tls_options(&self) -> TlsOptions {
+        TlsOptions {
+            ca_cert: self.ca_cert.clone(),
+            client_cert: self.client_cert.clone(),
+            client_key: self.client_key.clone(),
+            client_cert_password: self.client_cert_password.clone().unwrap_or_default(),
+            insecure: self.insecure,
+            sni: self.sni.clone(),
+        }
+    }
+
+    fn resolve_options(&self) -> ResolveOptions {
+        let family = match (self.ipv4, self.ipv6) {
+            (true, _) => Some(IpFamily::V4),
+            (_, true) => Some(IpFamily::V6),
+            _ => None,
+        };
+        ResolveOptions {
+            overrides: self.resolve.clone(),
+            family,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send messages and print the frames received, without the TUI, for scripts.
+    ///
+    /// Exits with 0 on success, 1 if a message could not be read or sent, 3 if the connection
+    /// failed, 4 on timeout and 5 if the connection ended before the awaited frames arrived.
+    Send(SendArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct SendArgs {
+    /// URL to connect to: `ws://`, `wss://`, or `ws+unix:///path/to.sock:/path` for a server
+    /// listening on a Unix domain socket.
+    #[arg(short, long)]
+    url: String,
+
+    #[command(flatten)]
+    connect: ConnectArgs,
+
+    /// Message to send, with the same syntax as in the TUI (e.g. `:hex 00ff`). Can be
+    /// repeated. Without it, messages are read from stdin, one per line.
+    #[arg(short, long = "message", value_name = "MESSAGE")]
+    messages: Vec<String>,
+
+    /// Exit once this many text or binary frames are received (0 to exit once everything is
+    /// sent). Without it, exit when the server closes the connection.
+    #[arg(short, long, value_name = "N")]
+    wait_for: Option<usize>,
+
+    /// Give up after this long, connecting included.
+    #[arg(short, long, value_name = "DURATION", value_parser = parse_duration)]
+    timeout: Option<Duration>,
+
+    /// How to print the frames received.
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Raw)]
+    format: OutputFormat,
+}
+
+/// Parses a duration such as `250ms`, `5s` or `2m`. A bare number is taken as seconds.
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration `{s}`"))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        _ => {
+            return Err(format!(
+                "invalid duration unit `{unit}`, use `ms`, `s` or `m`"
+            ))
+        }
+    };
+
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
 }
 
 #[tokio::main]
-async fn main() -> color_eyre::Result<()> {
+async fn main() -> color_eyre::Result<ExitCode> {
     color_eyre::install()?;
 
     let args = Args::parse();
-    let url = args.url.unwrap_or_else(|| "".to_string());
+    if let Some(Command::Send(send)) = args.command {
+        let options = ConnectOptions {
+            url: send.url,
+            tls: send.connect.tls_options(),
+            resolve: send.connect.resolve_options(),
+            headers: send.connect.headers,
+            protocols: send.connect.protocols,
+            proxy: send.connect.proxy,
+        };
+        if options.tls.insecure {
+            eprintln!("Warning: TLS certificate verification is disabled (--insecure)");
+        }
+        let headless_options = HeadlessOptions {
+            messages: send.messages,
+            wait_for: send.wait_for,
+            timeout: send.timeout,
+            format: send.format,
+        };
+        return Ok(headless::run(options, headless_options).await.into());
+    }
+
+    let options = ConnectOptions {
+        url: args.url.unwrap_or_else(|| "".to_string()),
+        tls: args.connect.tls_options(),
+        resolve: args.connect.resolve_options(),
+        headers: args.connect.headers,
+        protocols: args.connect.protocols,
+        proxy: args.connect.proxy,
+    };
+    let session_options = SessionOptions {
+        reconnect: args.reconnect.then_some(ReconnectPolicy {
+            max_attempts: args.max_reconnect_attempts,
+            initial_delay: args.reconnect_delay,
+            max_delay: args.max_reconnect_delay,
+        }),
+        on_open: args.on_open,
+        ping_interval: args.ping_interval,
+        pong_timeout: args.pong_timeout,
+    };
+
+    let transcript = args
+        .log
+        .map(|path| {
+            Transcript::append(&path, &options.url)
+                .wrap_err_with(|| format!("could not open log file {}", path.display()))
+        })
+        .transpose()?;
+
+    let recording = args
+        .replay
+        .map(|path| {
+            Recording::load(&path)
+                .map_err(|e| eyre!("could not load transcript {}: {e}", path.display()))
+        })
+        .transpose()?;
 
     let terminal = ratatui::init();
-    let result = App::new(url).run(terminal).await;
+    // The panic hook installed by ratatui only leaves raw mode and the alternate screen.
+    let restore = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = disable_terminal_features();
+        restore(info);
+    }));
+    let result = match enable_terminal_features() {
+        Ok(()) => {
+            App::new(
+                options,
+                session_options,
+                args.timestamps,
+                transcript,
+                recording,
+                args.replay_timing,
+            )
+            .run(terminal)
+            .await
+        }
+        Err(e) => Err(e.into()),
+    };
 
+    // The terminal is restored whatever happened, before the first error is reported.
+    let disabled = disable_terminal_features();
     ratatui::restore();
-    result
+    result?;
+    disabled?;
+    Ok(ExitCode::SUCCESS)
 }
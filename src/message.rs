@@ -0,0 +1,428 @@
+use std::{
+    fmt::Write,
+    time::{Duration, Instant, SystemTime},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use serde_json::Value;
+use tokio_websockets::{CloseCode, Message};
+
+use crate::json;
+
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub author: Author,
+    pub content: Content,
+    /// When the message was sent or received.
+    pub timestamp: Timestamp,
+    /// The text payload, if it is a JSON document.
+    pub json: Option<Value>,
+    /// Whether `json` is shown indented over several lines rather than on a single one.
+    pub expanded: bool,
+}
+
+impl ChatMessage {
+    pub fn new(author: Author, content: Content) -> Self {
+        let json = match (author, &content) {
+            (Author::User | Author::Origin, Content::Text(text)) => json::parse_document(text),
+            _ => None,
+        };
+
+        ChatMessage {
+            author,
+            content,
+            timestamp: Timestamp::now(),
+            json,
+            expanded: true,
+        }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        ChatMessage::new(Author::System, Content::Text(content.into()))
+    }
+
+    /// The message as it should be displayed, with binary payloads shown in the given view.
+    pub fn render(&self, view: BinaryView) -> String {
+        match &self.content {
+            Content::Text(text) => text.clone(),
+            Content::Binary(data) => {
+                let body = match view {
+                    BinaryView::Hex => hex_dump(data),
+                    BinaryView::Base64 => BASE64.encode(data),
+                    BinaryView::Utf8Lossy => String::from_utf8_lossy(data).into_owned(),
+                };
+                format!("[binary, {} bytes, {}]\n{body}", data.len(), view.name())
+            }
+            Content::Ping(payload) => format!("[ping] {}", display_payload(payload)),
+            Content::Pong { payload, rtt } => match rtt {
+                Some(rtt) => format!(
+                    "[pong] {} (round trip {:.1} ms)",
+                    display_payload(payload),
+                    rtt.as_secs_f64() * 1000.0
+                ),
+                None => format!("[pong] {}", display_payload(payload)),
+            },
+            Content::Close {
+                code: Some(code),
+                reason,
+            } => {
+                format!("[close] code {}: {reason}", u16::from(*code))
+            }
+            Content::Close { code: None, .. } => "[close] no status code".to_string(),
+        }
+    }
+}
+
+/// Adds `message` to the log, keeping it sorted by time. A sent message is only added once the
+/// send completes, by which point the reply may already have been logged.
+pub fn insert_chronologically(messages: &mut Vec<ChatMessage>, message: ChatMessage) {
+    let position = messages
+        .iter()
+        .rposition(|m| m.timestamp.mono <= message.timestamp.mono)
+        .map_or(0, |i| i + 1);
+
+    messages.insert(position, message);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Author {
+    User,
+    Origin,
+    /// Lifecycle events of the connection, such as it being opened or closed.
+    System,
+}
+
+/// The contents of a frame.
+#[derive(Debug, Clone)]
+pub enum Content {
+    Text(String),
+    Binary(Vec<u8>),
+    Ping(Vec<u8>),
+    Pong {
+        payload: Vec<u8>,
+        /// Time since the matching ping was sent, if it was sent by us.
+        rtt: Option<Duration>,
+    },
+    Close {
+        code: Option<CloseCode>,
+        reason: String,
+    },
+}
+
+impl Content {
+    /// Parses what was typed in the message input. It is sent as text, unless prefixed by
+    /// `:hex ` or `:b64 `, in which case the rest is decoded and sent as a binary frame.
+    /// `:text ` can be used to send text that would otherwise look like a prefix.
+    ///
+    /// Control frames are sent with `:ping [payload]`, `:pong [payload]` and
+    /// `:close [code] [reason]`.
+    pub fn parse_input(input: &str) -> Result<Self, &'static str> {
+        if let Some(payload) = command(input, ":ping") {
+            control_payload(payload).map(Content::Ping)
+        } else if let Some(payload) = command(input, ":pong") {
+            control_payload(payload).map(|payload| Content::Pong { payload, rtt: None })
+        } else if let Some(args) = command(input, ":close") {
+            let (code, reason) = args.split_once(' ').unwrap_or((args, ""));
+            if code.is_empty() {
+                return Ok(Content::Close {
+                    code: None,
+                    reason: String::new(),
+                });
+            }
+
+            let code = code
+                .parse::<u16>()
+                .ok()
+                .and_then(|code| CloseCode::try_from(code).ok())
+                .filter(|code| !code.is_reserved())
+                .ok_or("INVALID CLOSE CODE!")?;
+            // The close payload also carries the 2 bytes of the code.
+            if reason.len() > 123 {
+                return Err("CLOSE REASON OVER 123 BYTES!");
+            }
+
+            Ok(Content::Close {
+                code: Some(code),
+                reason: reason.to_string(),
+            })
+        } else if let Some(hex) = input.strip_prefix(":hex ") {
+            parse_hex(hex).map(Content::Binary)
+        } else if let Some(b64) = input.strip_prefix(":b64 ") {
+            BASE64
+                .decode(b64.trim())
+                .map(Content::Binary)
+                .map_err(|_| "INVALID BASE64 PAYLOAD!")
+        } else if let Some(text) = input.strip_prefix(":text ") {
+            Ok(Content::Text(text.to_string()))
+        } else {
+            Ok(Content::Text(input.to_string()))
+        }
+    }
+
+    /// The contents of `message`, or `None` for frames that are not meant to be shown
+    /// (continuation frames are already assembled by the library).
+    pub fn from_message(message: &Message) -> Option<Self> {
+        let payload = message.as_payload().to_vec();
+
+        if let Some(text) = message.as_text() {
+            Some(Content::Text(text.to_string()))
+        } else if message.is_binary() {
+            Some(Content::Binary(payload))
+        } else if message.is_ping() {
+            Some(Content::Ping(payload))
+        } else if message.is_pong() {
+            Some(Content::Pong { payload, rtt: None })
+        } else if let Some((code, reason)) = message.as_close() {
+            Some(Content::Close {
+                code: (!payload.is_empty()).then_some(code),
+                reason: reason.to_string(),
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn to_message(&self) -> Message {
+        match self {
+            Content::Text(text) => Message::text(text.clone()),
+            Content::Binary(data) => Message::binary(data.clone()),
+            Content::Ping(payload) => Message::ping(payload.clone()),
+            Content::Pong { payload, .. } => Message::pong(payload.clone()),
+            Content::Close { code, reason } => Message::close(*code, reason),
+        }
+    }
+
+    /// The opcode of the frame carrying this content, and its name.
+    pub fn opcode(&self) -> (u8, &'static str) {
+        match self {
+            Content::Text(_) => (0x1, "text"),
+            Content::Binary(_) => (0x2, "binary"),
+            Content::Close { .. } => (0x8, "close"),
+            Content::Ping(_) => (0x9, "ping"),
+            Content::Pong { .. } => (0xA, "pong"),
+        }
+    }
+
+    /// The payload as it goes on the wire, e.g. with the close code in front of the reason.
+    pub fn payload(&self) -> Vec<u8> {
+        match self {
+            Content::Text(text) => text.as_bytes().to_vec(),
+            Content::Binary(payload) | Content::Ping(payload) | Content::Pong { payload, .. } => {
+                payload.clone()
+            }
+            Content::Close { code: None, .. } => Vec::new(),
+            Content::Close {
+                code: Some(code),
+                reason,
+            } => {
+                let mut payload = u16::from(*code).to_be_bytes().to_vec();
+                payload.extend_from_slice(reason.as_bytes());
+                payload
+            }
+        }
+    }
+}
+
+/// Returns the arguments of `input` if it is the given command, i.e. `name` alone or followed
+/// by a space.
+fn command<'a>(input: &'a str, name: &str) -> Option<&'a str> {
+    let rest = input.strip_prefix(name)?;
+
+    if rest.is_empty() {
+        Some(rest)
+    } else {
+        rest.strip_prefix(' ')
+    }
+}
+
+fn control_payload(payload: &str) -> Result<Vec<u8>, &'static str> {
+    if payload.len() > 125 {
+        return Err("CONTROL PAYLOAD OVER 125 BYTES!");
+    }
+
+    Ok(payload.as_bytes().to_vec())
+}
+
+/// Shows a control frame payload as text if possible, and as hex otherwise.
+fn display_payload(payload: &[u8]) -> String {
+    if payload.is_empty() {
+        return "(empty)".to_string();
+    }
+
+    match std::str::from_utf8(payload) {
+        Ok(text) => text.to_string(),
+        Err(_) => payload
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+/// A point in time, both as wall clock (for display) and monotonic (for measuring intervals).
+#[derive(Debug, Clone, Copy)]
+pub struct Timestamp {
+    pub wall: SystemTime,
+    pub mono: Instant,
+}
+
+impl Timestamp {
+    pub fn now() -> Self {
+        Timestamp {
+            wall: SystemTime::now(),
+            mono: Instant::now(),
+        }
+    }
+
+    /// Formats the wall clock time as an RFC 3339 UTC date and time, such as
+    /// `2024-05-01T12:34:56.789Z`.
+    pub fn format_rfc3339(&self) -> String {
+        DateTime::<Utc>::from(self.wall).to_rfc3339_opts(SecondsFormat::Millis, true)
+    }
+
+    /// Formats the wall clock time as `HH:MM:SS.mmm`, in local time.
+    pub fn format_wall(&self) -> String {
+        DateTime::<Local>::from(self.wall)
+            .format("%H:%M:%S%.3f")
+            .to_string()
+    }
+}
+
+/// Parses an RFC 3339 date and time, such as those written by [`Timestamp::format_rfc3339`].
+pub fn parse_rfc3339(s: &str) -> Option<SystemTime> {
+    DateTime::parse_from_rfc3339(s).ok().map(SystemTime::from)
+}
+
+/// How message timestamps are shown in the message log.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TimestampFormat {
+    None,
+    /// Wall clock time, as `HH:MM:SS.mmm`.
+    #[default]
+    Absolute,
+    /// Time since the current connection was opened.
+    Relative,
+    /// Time since the previous message.
+    Delta,
+}
+
+impl TimestampFormat {
+    pub fn next(self) -> Self {
+        match self {
+            TimestampFormat::None => TimestampFormat::Absolute,
+            TimestampFormat::Absolute => TimestampFormat::Relative,
+            TimestampFormat::Relative => TimestampFormat::Delta,
+            TimestampFormat::Delta => TimestampFormat::None,
+        }
+    }
+
+    /// The prefix for a message at `timestamp`, or `None` if timestamps are hidden.
+    pub fn format(
+        self,
+        timestamp: &Timestamp,
+        previous: Option<&Timestamp>,
+        opened_at: Option<Instant>,
+    ) -> Option<String> {
+        let signed = |from: Instant, to: Instant| match to.checked_duration_since(from) {
+            Some(d) => format!("+{:.3}s", d.as_secs_f64()),
+            None => format!("-{:.3}s", (from - to).as_secs_f64()),
+        };
+
+        match self {
+            TimestampFormat::None => None,
+            TimestampFormat::Absolute => Some(timestamp.format_wall()),
+            TimestampFormat::Relative => Some(match opened_at {
+                Some(opened_at) => signed(opened_at, timestamp.mono),
+                None => "--".to_string(),
+            }),
+            TimestampFormat::Delta => Some(format!(
+                "Δ{}",
+                signed(previous.map_or(timestamp.mono, |p| p.mono), timestamp.mono)
+            )),
+        }
+    }
+}
+
+/// How binary payloads are displayed in the message log.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryView {
+    #[default]
+    Hex,
+    Base64,
+    Utf8Lossy,
+}
+
+impl BinaryView {
+    pub fn next(self) -> Self {
+        match self {
+            BinaryView::Hex => BinaryView::Base64,
+            BinaryView::Base64 => BinaryView::Utf8Lossy,
+            BinaryView::Utf8Lossy => BinaryView::Hex,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BinaryView::Hex => "hex",
+            BinaryView::Base64 => "base64",
+            BinaryView::Utf8Lossy => "utf-8",
+        }
+    }
+}
+
+/// Formats `data` like `hexdump -C`: offset, 16 bytes in hex and their printable ASCII.
+pub fn hex_dump(data: &[u8]) -> String {
+    let mut dump = String::new();
+
+    for (i, chunk) in data.chunks(16).enumerate() {
+        if i > 0 {
+            dump.push('\n');
+        }
+        let _ = write!(dump, "{:08x}  ", i * 16);
+        for j in 0..16 {
+            match chunk.get(j) {
+                Some(b) => {
+                    let _ = write!(dump, "{b:02x} ");
+                }
+                None => dump.push_str("   "),
+            }
+            if j == 7 {
+                dump.push(' ');
+            }
+        }
+        dump.push_str(" |");
+        dump.extend(chunk.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        }));
+        dump.push('|');
+    }
+
+    dump
+}
+
+/// Parses hex digits into bytes, ignoring whitespace and an optional `0x` on each byte group.
+pub fn parse_hex(hex: &str) -> Result<Vec<u8>, &'static str> {
+    let digits: String = hex
+        .split_whitespace()
+        .map(|group| group.trim_start_matches("0x"))
+        .collect();
+
+    if !digits.len().is_multiple_of(2) {
+        return Err("ODD NUMBER OF HEX DIGITS!");
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            digits
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or("INVALID HEX PAYLOAD!")
+        })
+        .collect()
+}
@@ -0,0 +1,267 @@
+use std::{
+    fs,
+    ops::Range,
+    path::Path,
+    sync::{Arc, Mutex as SyncMutex},
+    time::{Duration, Instant},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tokio_websockets::CloseCode;
+
+use crate::{
+    json,
+    message::{parse_rfc3339, Content},
+    session::Session,
+};
+
+/// How fast recorded messages are sent again.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReplayTiming {
+    /// With the delays between them in the recording.
+    #[default]
+    Original,
+    /// One after the other, as fast as possible.
+    Fast,
+}
+
+impl ReplayTiming {
+    pub fn name(self) -> &'static str {
+        match self {
+            ReplayTiming::Original => "original timing",
+            ReplayTiming::Fast => "as fast as possible",
+        }
+    }
+}
+
+/// The messages of a session recorded with `--log` or exported from the app.
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    /// Messages sent, with the time elapsed between the first one and them.
+    pub sent: Vec<(Duration, Content)>,
+    /// Messages received.
+    pub received: Vec<Content>,
+}
+
+impl Recording {
+    /// Reads a JSON Lines transcript. System messages are left out.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let file = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let mut recording = Recording::default();
+        let mut first_sent = None;
+
+        for (i, line) in file.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = json::parse(line).ok_or(format!("line {}: invalid JSON", i + 1))?;
+            let field = |name: &str| {
+                record
+                    .get(name)
+                    .and_then(Value::as_str)
+                    .ok_or(format!("line {}: missing `{name}`", i + 1))
+            };
+
+            let direction = field("direction")?;
+            if direction == "system" {
+                continue;
+            }
+            let content =
+                content(&record).ok_or(format!("line {}: invalid frame type or payload", i + 1))?;
+
+            match direction {
+                "sent" => {
+                    let timestamp = parse_rfc3339(field("timestamp")?)
+                        .ok_or(format!("line {}: invalid timestamp", i + 1))?;
+                    let first = *first_sent.get_or_insert(timestamp);
+                    let offset = timestamp.duration_since(first).unwrap_or_default();
+                    recording.sent.push((offset, content));
+                }
+                "received" => recording.received.push(content),
+                _ => return Err(format!("line {}: unknown direction `{direction}`", i + 1)),
+            }
+        }
+
+        if recording.sent.is_empty() {
+            return Err("no sent messages to replay".to_string());
+        }
+        Ok(recording)
+    }
+}
+
+/// The frame recorded in `record`, as written by `transcript::record`.
+fn content(record: &Value) -> Option<Content> {
+    let payload = || {
+        let payload = record.get("payload")?.as_str()?;
+        match record.get("encoding").and_then(Value::as_str) {
+            None => Some(payload.as_bytes().to_vec()),
+            Some("base64") => BASE64.decode(payload).ok(),
+            Some(_) => None,
+        }
+    };
+
+    match record.get("type")?.as_str()? {
+        "text" => Some(Content::Text(String::from_utf8(payload()?).ok()?)),
+        "binary" => Some(Content::Binary(payload()?)),
+        "ping" => Some(Content::Ping(payload()?)),
+        "pong" => Some(Content::Pong {
+            payload: payload()?,
+            rtt: None,
+        }),
+        "close" => {
+            let code = match record.get("code")? {
+                Value::Null => None,
+                Value::Number(code) => {
+                    let code = u16::try_from(code.as_u64()?).ok()?;
+                    Some(CloseCode::try_from(code).ok()?)
+                }
+                _ => return None,
+            };
+            let reason = record.get("reason").and_then(Value::as_str);
+            Some(Content::Close {
+                code,
+                reason: reason.unwrap_or_default().to_string(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// How far a replay has gone.
+#[derive(Debug, Default)]
+pub struct Progress {
+    /// How many recorded messages have been sent.
+    pub sent: usize,
+    /// Why the replay stopped early, if it did.
+    pub error: Option<&'static str>,
+}
+
+/// A recording being sent again on the current connection. The replay stops when dropped.
+#[derive(Debug)]
+pub struct Replay {
+    pub recording: Arc<Recording>,
+    pub timing: ReplayTiming,
+    /// Messages received since then are the live responses to compare with the recorded ones.
+    pub started: Instant,
+    pub progress: Arc<SyncMutex<Progress>>,
+    task: JoinHandle<()>,
+}
+
+impl Replay {
+    pub fn start(recording: Arc<Recording>, session: Session, timing: ReplayTiming) -> Self {
+        let started = Instant::now();
+        let progress = Arc::new(SyncMutex::new(Progress::default()));
+
+        let task = tokio::spawn({
+            let recording = Arc::clone(&recording);
+            let progress = Arc::clone(&progress);
+            async move {
+                for (offset, content) in &recording.sent {
+                    if timing == ReplayTiming::Original {
+                        tokio::time::sleep_until((started + *offset).into()).await;
+                    }
+                    if let Err(e) = session.send_logged(content.clone()).await {
+                        progress.lock().unwrap().error = Some(e);
+                        return;
+                    }
+                    progress.lock().unwrap().sent += 1;
+                }
+            }
+        });
+
+        Replay {
+            recording,
+            timing,
+            started,
+            progress,
+            task,
+        }
+    }
+}
+
+impl Drop for Replay {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Whether a live response is the same as the recorded one. JSON payloads are compared by
+/// value, so that formatting and the order of keys do not count as differences.
+pub fn same_response(recorded: &Content, live: &Content) -> bool {
+    if recorded.opcode() != live.opcode() {
+        return false;
+    }
+
+    match (recorded, live) {
+        (Content::Text(recorded), Content::Text(live)) if recorded != live => {
+            match (json::parse_document(recorded), json::parse_document(live)) {
+                (Some(recorded), Some(live)) => json::same_value(&recorded, &live),
+                _ => false,
+            }
+        }
+        _ => recorded.payload() == live.payload(),
+    }
+}
+
+/// The parts of `a` and `b` that differ, as byte ranges, once their common beginning and end
+/// are left out.
+pub fn differing_ranges(a: &str, b: &str) -> (Range<usize>, Range<usize>) {
+    let prefix = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i);
+    let suffix: usize = a[prefix..]
+        .chars()
+        .rev()
+        .zip(b[prefix..].chars().rev())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len_utf8())
+        .sum();
+
+    (prefix..a.len() - suffix, prefix..b.len() - suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_differing_ranges() {
+        assert_eq!(differing_ranges("same", "same"), (4..4, 4..4));
+        assert_eq!(differing_ranges("", "new"), (0..0, 0..3));
+        assert_eq!(differing_ranges("tick 1", "tick 12"), (6..6, 6..7));
+        assert_eq!(differing_ranges("tick 12", "tick 2"), (5..6, 5..5));
+        // A repeated character is shared by the prefix only, not also by the suffix.
+        assert_eq!(differing_ranges("aa", "aaa"), (2..2, 2..3));
+    }
+
+    #[test]
+    fn finds_differing_ranges_in_multibyte_text() {
+        assert_eq!(differing_ranges("héllo", "hallo"), (1..3, 1..2));
+        assert_eq!(differing_ranges("日本語", "日本人"), (6..9, 6..9));
+        assert_eq!(differing_ranges("→ ok", "⇒ ok"), (0..3, 0..3));
+        assert_eq!(differing_ranges("naïve", "naïveté"), (6..6, 6..9));
+        // Characters sharing their last byte differ as a whole.
+        assert_eq!(differing_ranges("é", "ā"), (0..2, 0..2));
+    }
+
+    #[test]
+    fn compares_responses() {
+        let text = |s: &str| Content::Text(s.to_string());
+        assert!(same_response(&text("hi"), &text("hi")));
+        assert!(!same_response(&text("hi"), &text("ho")));
+        assert!(same_response(
+            &text(r#"{"a": 1, "b": [true]}"#),
+            &text(r#"{"b":[true],"a":1.0}"#)
+        ));
+        assert!(!same_response(&text(r#"{"a": 1}"#), &text(r#"{"a": 2}"#)));
+        assert!(!same_response(&text("1"), &Content::Binary(b"1".to_vec())));
+        assert!(same_response(
+            &Content::Binary(vec![0, 1]),
+            &Content::Binary(vec![0, 1])
+        ));
+    }
+}
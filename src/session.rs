@@ -0,0 +1,401 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::Sender,
+        Arc, Mutex as SyncMutex,
+    },
+    time::{Duration, Instant},
+};
+
+use futures_util::{
+    stream::{SplitSink, SplitStream},
+    SinkExt, StreamExt,
+};
+use tokio::sync::Mutex;
+use tokio_websockets::{CloseCode, Message};
+
+use crate::{
+    connection::{connect, ConnectError, ConnectOptions, ConnectionState, Handshake, WS},
+    message::{Author, ChatMessage, Content},
+};
+
+pub type ArcSink = Arc<Mutex<Option<SplitSink<WS, Message>>>>;
+
+/// Shared view of the connection lifecycle, updated by the connecting and streaming tasks.
+#[derive(Debug, Default)]
+pub struct Status {
+    /// Incremented on every (re)connection requested by the user, so that tasks belonging to
+    /// a previous connection can tell that they are stale.
+    pub generation: u64,
+    pub state: ConnectionState,
+    /// When the current connection was opened.
+    pub opened_at: Option<Instant>,
+    /// When a frame was last received on the current connection.
+    pub last_traffic: Option<Instant>,
+    /// Round trip of the last keepalive ping.
+    pub last_rtt: Option<Duration>,
+}
+
+/// The outcome of the last connection attempt, `None` while it is still in progress.
+pub type ConnectionReport = Arc<SyncMutex<Option<Result<Handshake, ConnectError>>>>;
+
+/// How to retry after the connection drops.
+#[derive(Debug, Clone, Copy)]
+pub struct ReconnectPolicy {
+    /// `None` retries forever.
+    pub max_attempts: Option<u32>,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl ReconnectPolicy {
+    /// Exponential backoff with jitter: the delay doubles on every attempt (up to `max_delay`)
+    /// and a random amount of up to half of it is taken off, so that many clients dropped at
+    /// once do not all come back at the same time.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self
+            .initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay);
+
+        delay.mul_f64(1.0 - fastrand::f64() / 2.0)
+    }
+}
+
+/// Settings that apply to the whole session rather than to a single connection.
+#[derive(Debug, Clone, Default)]
+pub struct SessionOptions {
+    /// Retry when the connection drops, if set.
+    pub reconnect: Option<ReconnectPolicy>,
+    /// Messages sent every time the connection opens, e.g. to subscribe to a topic.
+    pub on_open: Vec<String>,
+    /// Ping the server this often, to keep idle connections from being dropped.
+    pub ping_interval: Option<Duration>,
+    /// Consider the connection dead when nothing is received for this long after a keepalive
+    /// ping.
+    pub pong_timeout: Option<Duration>,
+}
+
+/// A ping still awaiting its pong.
+#[derive(Debug, Clone, Copy)]
+struct SentPing {
+    at: Instant,
+    /// Keepalive pings and their pongs are not shown in the message log.
+    keepalive: bool,
+}
+
+/// Handles shared between the UI and the tasks driving the connection.
+#[derive(Clone)]
+pub struct Session {
+    pub sink: ArcSink,
+    pub status: Arc<SyncMutex<Status>>,
+    pub report: ConnectionReport,
+    sender: Sender<ChatMessage>,
+    options: Arc<SessionOptions>,
+    /// Pings still awaiting their pong, by payload. Cleared on each new connection.
+    pings: Arc<SyncMutex<HashMap<Vec<u8>, SentPing>>>,
+    ping_counter: Arc<AtomicU64>,
+}
+
+impl Session {
+    pub fn new(sender: Sender<ChatMessage>, options: SessionOptions) -> Self {
+        Session {
+            sink: Arc::new(Mutex::new(None)),
+            status: Arc::new(SyncMutex::new(Status::default())),
+            report: Arc::new(SyncMutex::new(None)),
+            sender,
+            options: Arc::new(options),
+            pings: Arc::new(SyncMutex::new(HashMap::new())),
+            ping_counter: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Closes the current connection (if any) and spawns a task that connects with `options`,
+    /// reconnecting according to the session's policy for as long as no other connection is
+    /// requested.
+    pub fn reconnect(&self, options: ConnectOptions) {
+        let generation = {
+            let mut status = self.status.lock().unwrap();
+            status.generation += 1;
+            status.state = ConnectionState::Connecting;
+            status.generation
+        };
+        *self.report.lock().unwrap() = None;
+
+        tokio::spawn(self.clone().run(options, generation));
+    }
+
+    /// Sends `content` on the current connection, keeping track of the pings sent (to measure
+    /// their round trip) and of closes initiated by us.
+    pub async fn send(&self, content: &Content) -> Result<(), &'static str> {
+        self.transmit(content, false).await
+    }
+
+    /// Sends `content` and adds it to the message log, as if it had been typed.
+    pub async fn send_logged(&self, content: Content) -> Result<(), &'static str> {
+        let message = ChatMessage::new(Author::User, content);
+        self.send(&message.content).await?;
+        self.sender.send(message).expect("channel should be open");
+
+        Ok(())
+    }
+
+    async fn transmit(&self, content: &Content, keepalive: bool) -> Result<(), &'static str> {
+        let mut sink = self.sink.lock().await;
+        let Some(sink) = sink.as_mut() else {
+            return Err("NOT CONNECTED! See connection info.");
+        };
+
+        match content {
+            Content::Ping(payload) => {
+                let ping = SentPing {
+                    at: Instant::now(),
+                    keepalive,
+                };
+                self.pings.lock().unwrap().insert(payload.clone(), ping);
+            }
+            Content::Close { .. } => self.status.lock().unwrap().state = ConnectionState::Closing,
+            _ => {}
+        }
+
+        sink.send(content.to_message())
+            .await
+            .map_err(|_| "ERROR SENDING MESSAGE!")
+    }
+
+    /// Sends a ping with a payload unique to this session, returning what was sent.
+    pub async fn ping(&self) -> Result<Content, &'static str> {
+        self.send_ping(false).await
+    }
+
+    async fn send_ping(&self, keepalive: bool) -> Result<Content, &'static str> {
+        let n = self.ping_counter.fetch_add(1, Ordering::Relaxed) + 1;
+        let ping = Content::Ping(format!("rsocktui-{n}").into_bytes());
+
+        self.transmit(&ping, keepalive).await.map(|_| ping)
+    }
+
+    fn is_current(&self, generation: u64) -> bool {
+        self.status.lock().unwrap().generation == generation
+    }
+
+    fn log(&self, content: impl Into<String>) {
+        self.sender
+            .send(ChatMessage::system(content))
+            .expect("channel should be open");
+    }
+
+    async fn run(self, options: ConnectOptions, generation: u64) {
+        if let Some(mut old_sink) = self.sink.lock().await.take() {
+            let _ = old_sink
+                .send(Message::close(Some(CloseCode::NORMAL_CLOSURE), ""))
+                .await;
+        }
+
+        let mut attempt = 0;
+
+        loop {
+            match connect(&options).await {
+                Ok((new_sink, stream, handshake)) => {
+                    {
+                        let mut sink = self.sink.lock().await;
+                        let mut status = self.status.lock().unwrap();
+                        if status.generation != generation {
+                            return;
+                        }
+
+                        status.state = ConnectionState::Open;
+                        status.opened_at = Some(Instant::now());
+                        status.last_traffic = status.opened_at;
+                        status.last_rtt = None;
+                        *sink = Some(new_sink);
+                        *self.report.lock().unwrap() = Some(Ok(handshake));
+                    }
+                    self.log(format!("Connected to {}", options.url));
+                    self.pings.lock().unwrap().clear();
+                    attempt = 0;
+
+                    self.send_on_open().await;
+
+                    let reason = tokio::select! {
+                        reason = self.stream(stream, generation) => match reason {
+                            Some(reason) => reason,
+                            None => return,
+                        },
+                        reason = self.keepalive(generation) => reason,
+                    };
+
+                    let mut sink = self.sink.lock().await;
+                    let mut status = self.status.lock().unwrap();
+                    if status.generation != generation {
+                        return;
+                    }
+                    let closed_by_us = status.state == ConnectionState::Closing;
+                    status.state = ConnectionState::Closed;
+                    *sink = None;
+                    drop(status);
+                    drop(sink);
+
+                    if closed_by_us {
+                        self.log("Connection closed");
+                        return;
+                    }
+                    self.log(reason);
+                }
+                Err(e) => {
+                    let mut status = self.status.lock().unwrap();
+                    if status.generation != generation {
+                        return;
+                    }
+                    status.state = ConnectionState::Closed;
+                    drop(status);
+
+                    self.log(format!("Connection failed: {e}"));
+                    let retryable = e.is_retryable();
+                    *self.report.lock().unwrap() = Some(Err(e));
+                    if !retryable {
+                        return;
+                    }
+                }
+            }
+
+            let Some(policy) = self.options.reconnect else {
+                return;
+            };
+
+            attempt += 1;
+            if policy.max_attempts.is_some_and(|max| attempt > max) {
+                self.log(format!("Giving up after {} attempts", attempt - 1));
+                return;
+            }
+
+            let delay = policy.delay(attempt);
+            let of = policy
+                .max_attempts
+                .map(|max| format!("/{max}"))
+                .unwrap_or_default();
+            self.log(format!(
+                "Reconnecting in {:.1}s (attempt {attempt}{of})",
+                delay.as_secs_f64()
+            ));
+            tokio::time::sleep(delay).await;
+
+            let mut status = self.status.lock().unwrap();
+            if status.generation != generation {
+                return;
+            }
+            status.state = ConnectionState::Connecting;
+            *self.report.lock().unwrap() = None;
+        }
+    }
+
+    /// Pings the server every `ping_interval` and returns once a ping is left unanswered for
+    /// `pong_timeout`, which is taken as the connection being dead. Any frame received counts
+    /// as an answer. Never returns without `ping_interval`.
+    async fn keepalive(&self, generation: u64) -> String {
+        let Some(interval) = self.options.ping_interval else {
+            return std::future::pending().await;
+        };
+        let timeout = self.options.pong_timeout;
+
+        let mut last_ping = Instant::now();
+        // The keepalive ping awaiting an answer, by payload, and when it was sent.
+        let mut unanswered: Option<(Vec<u8>, Instant)> = None;
+        loop {
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            if !self.is_current(generation) {
+                return "Connection superseded".to_string();
+            }
+
+            if let Some((payload, sent_at)) = &unanswered {
+                let pong_received = !self.pings.lock().unwrap().contains_key(payload);
+                let traffic_since = self
+                    .status
+                    .lock()
+                    .unwrap()
+                    .last_traffic
+                    .is_some_and(|t| t > *sent_at);
+
+                // The ping stays in `pings` even if other traffic answered it, so that its pong
+                // is still recognized (and hidden) should it arrive late.
+                if pong_received || traffic_since {
+                    unanswered = None;
+                } else if timeout.is_some_and(|timeout| sent_at.elapsed() >= timeout) {
+                    return format!(
+                        "Keepalive ping unanswered for {:.1}s, connection considered dead",
+                        sent_at.elapsed().as_secs_f64()
+                    );
+                }
+            }
+
+            // Without a timeout, a ping left unanswered is simply replaced by the next one.
+            if last_ping.elapsed() >= interval && (unanswered.is_none() || timeout.is_none()) {
+                if let Ok(Content::Ping(payload)) = self.send_ping(true).await {
+                    unanswered = Some((payload, Instant::now()));
+                }
+                last_ping = Instant::now();
+            }
+        }
+    }
+
+    async fn send_on_open(&self) {
+        for m in &self.options.on_open {
+            if let Err(e) = self.send_logged(Content::Text(m.clone())).await {
+                self.log(format!("Failed to send on-open message: {e}"));
+                return;
+            }
+        }
+    }
+
+    /// Forwards incoming messages to the log until the connection ends, returning the reason
+    /// why it did, or `None` if the connection was superseded by another one in the meantime.
+    async fn stream(&self, mut stream: SplitStream<WS>, generation: u64) -> Option<String> {
+        loop {
+            match stream.next().await {
+                Some(Ok(m)) => {
+                    if !self.is_current(generation) {
+                        return None;
+                    }
+                    self.status.lock().unwrap().last_traffic = Some(Instant::now());
+
+                    let Some(mut content) = Content::from_message(&m) else {
+                        continue;
+                    };
+                    if let Content::Pong { payload, rtt } = &mut content {
+                        let sent = self.pings.lock().unwrap().remove(payload);
+                        *rtt = sent.map(|sent| sent.at.elapsed());
+
+                        if sent.is_some_and(|sent| sent.keepalive) {
+                            self.status.lock().unwrap().last_rtt = *rtt;
+                            continue;
+                        }
+                    }
+
+                    let close = match &content {
+                        Content::Close {
+                            code: Some(code),
+                            reason,
+                        } => Some(format!(
+                            "Closed by server with code {}: {reason}",
+                            u16::from(*code)
+                        )),
+                        Content::Close { code: None, .. } => {
+                            Some("Closed by server without status code".to_string())
+                        }
+                        _ => None,
+                    };
+                    self.sender
+                        .send(ChatMessage::new(Author::Origin, content))
+                        .expect("channel should be open");
+                    if close.is_some() {
+                        return close;
+                    }
+                }
+                Some(Err(e)) => return Some(format!("Connection error: {e}")),
+                None => return Some("Stream ended".to_string()),
+            }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde_json::{Map, Value};
+
+use crate::message::{Author, ChatMessage, Content};
+
+/// A file the messages of the session are appended to as they come, as JSON Lines.
+#[derive(Debug)]
+pub struct Transcript {
+    file: File,
+    path: PathBuf,
+    /// The URL of the connection the messages belong to.
+    url: String,
+    /// Why the last message could not be written, if it could not.
+    error: Option<String>,
+}
+
+impl Transcript {
+    /// Opens `path` for appending, creating it if needed.
+    pub fn append(path: &Path, url: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Transcript {
+            file,
+            path: path.to_path_buf(),
+            url: url.to_string(),
+            error: None,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// Sets the URL recorded along with the messages that follow.
+    pub fn set_url(&mut self, url: &str) {
+        self.url = url.to_string();
+    }
+
+    /// Appends `message`. Failing to do so should not end the session, so the error is kept
+    /// to be shown instead of being returned.
+    pub fn write(&mut self, message: &ChatMessage) {
+        let line = record(message, &self.url) + "\n";
+        self.error = self
+            .file
+            .write_all(line.as_bytes())
+            .err()
+            .map(|e| e.to_string());
+    }
+}
+
+/// Writes `messages` to a new file at `path`, refusing to overwrite an existing one.
+pub fn export(path: &Path, messages: &[ChatMessage], url: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    let lines: String = messages.iter().map(|m| record(m, url) + "\n").collect();
+
+    file.write_all(lines.as_bytes())
+}
+
+/// `message` as a single line JSON object, such as
+/// `{"timestamp":"2024-05-01T12:34:56.789Z","url":"wss://example.com","direction":"received","type":"text","payload":"hello"}`.
+///
+/// Text payloads are written as strings, other payloads in base64 (marked by
+/// `"encoding": "base64"`), and close frames as their `code` and `reason`.
+pub fn record(message: &ChatMessage, url: &str) -> String {
+    let direction = match message.author {
+        Author::User => "sent",
+        Author::Origin => "received",
+        Author::System => "system",
+    };
+    let (_, frame_type) = message.content.opcode();
+    let mut record = Map::new();
+    record.insert(
+        "timestamp".into(),
+        message.timestamp.format_rfc3339().into(),
+    );
+    record.insert("url".into(), url.into());
+    record.insert("direction".into(), direction.into());
+    record.insert("type".into(), frame_type.into());
+
+    match &message.content {
+        Content::Text(text) => {
+            record.insert("payload".into(), text.as_str().into());
+        }
+        Content::Binary(payload) | Content::Ping(payload) | Content::Pong { payload, .. } => {
+            record.insert("payload".into(), BASE64.encode(payload).into());
+            record.insert("encoding".into(), "base64".into());
+        }
+        Content::Close { code, reason } => {
+            record.insert("code".into(), code.map(u16::from).into());
+            record.insert("reason".into(), reason.as_str().into());
+        }
+    }
+    if let Content::Pong { rtt: Some(rtt), .. } = &message.content {
+        // Microseconds are precise enough.
+        let rtt_ms = (rtt.as_secs_f64() * 1_000_000.0).round() / 1000.0;
+        record.insert("rtt_ms".into(), rtt_ms.into());
+    }
+
+    Value::Object(record).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio_websockets::CloseCode;
+
+    use super::*;
+    use crate::replay::Recording;
+
+    #[test]
+    fn recordings_read_back_transcripts() {
+        let messages = [
+            ChatMessage::new(Author::User, Content::Text("héllo \"world\"\n".to_string())),
+            ChatMessage::new(Author::Origin, Content::Binary(vec![0, 159, 255])),
+            ChatMessage::new(Author::System, Content::Text("Connected".to_string())),
+            ChatMessage::new(Author::User, Content::Ping(b"ping".to_vec())),
+            ChatMessage::new(
+                Author::Origin,
+                Content::Pong {
+                    payload: b"ping".to_vec(),
+                    rtt: Some(Duration::from_micros(1234)),
+                },
+            ),
+            ChatMessage::new(
+                Author::User,
+                Content::Close {
+                    code: Some(CloseCode::NORMAL_CLOSURE),
+                    reason: "bye".to_string(),
+                },
+            ),
+            ChatMessage::new(
+                Author::Origin,
+                Content::Close {
+                    code: None,
+                    reason: String::new(),
+                },
+            ),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("transcript.jsonl");
+        export(&path, &messages, "ws://localhost:9001").unwrap();
+
+        let recording = Recording::load(&path).unwrap();
+        let sent: Vec<_> = recording.sent.iter().map(|(_, content)| content).collect();
+        assert!(matches!(sent[..], [
+            Content::Text(text),
+            Content::Ping(ping),
+            Content::Close { code: Some(code), reason },
+        ] if text == "héllo \"world\"\n"
+            && ping == b"ping"
+            && *code == CloseCode::NORMAL_CLOSURE
+            && reason == "bye"));
+        assert!(matches!(&recording.received[..], [
+            Content::Binary(binary),
+            Content::Pong { payload, rtt: None },
+            Content::Close { code: None, reason },
+        ] if binary == &[0, 159, 255] && payload == b"ping" && reason.is_empty()));
+    }
+
+    #[test]
+    fn records_round_trip_times() {
+        let pong = ChatMessage::new(
+            Author::Origin,
+            Content::Pong {
+                payload: Vec::new(),
+                rtt: Some(Duration::from_micros(1234)),
+            },
+        );
+        let record: Value = serde_json::from_str(&record(&pong, "ws://localhost")).unwrap();
+        assert_eq!(record["rtt_ms"].to_string(), "1.234");
+        assert_eq!(record["encoding"], "base64");
+    }
+}